@@ -0,0 +1,135 @@
+use crate::scoring::{LockScore, TSpin};
+
+/// How many lines of garbage a lock sends to the opponent.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum AttackTable {
+    /// No bonus for perfect clears.
+    #[default]
+    Guideline,
+    /// TETR.IO: combos multiply the attack instead of adding to it.
+    TetrIo,
+    PuyoPuyoTetris,
+}
+
+impl AttackTable {
+    pub fn attack(&self, lock: &LockScore) -> u32 {
+        let clear = lock.clear;
+        if clear.lines == 0 {
+            return 0;
+        }
+        let mut lines = base_attack(clear.lines, clear.tspin);
+        if lock.back_to_back {
+            lines += 1;
+        }
+        let combo = lock.combo.unwrap_or(0);
+        lines = match self {
+            AttackTable::Guideline => lines + guideline_combo_attack(combo),
+            AttackTable::TetrIo => tetr_io_combo_attack(lines, combo),
+            AttackTable::PuyoPuyoTetris => lines + puyo_puyo_tetris_combo_attack(combo),
+        };
+        // The guideline attack table predates perfect clear bonuses, which
+        // later games added on top, so it only pays for the lines cleared.
+        if clear.perfect_clear && *self != AttackTable::Guideline {
+            lines += 10;
+        }
+        lines
+    }
+}
+
+fn base_attack(lines: usize, tspin: TSpin) -> u32 {
+    match (tspin, lines) {
+        (_, 0) => 0,
+        (TSpin::None, 1) => 0,
+        (TSpin::None, 2) => 1,
+        (TSpin::None, 3) => 2,
+        (TSpin::None, _) => 4,
+        (TSpin::Mini, 1) => 0,
+        (TSpin::Mini, _) => 1,
+        (TSpin::Full, 1) => 2,
+        (TSpin::Full, 2) => 4,
+        (TSpin::Full, _) => 6,
+    }
+}
+
+fn guideline_combo_attack(combo: u32) -> u32 {
+    match combo {
+        0 | 1 => 0,
+        2 | 3 => 1,
+        4 | 5 => 2,
+        6 | 7 => 3,
+        8..=10 => 4,
+        _ => 5,
+    }
+}
+
+fn puyo_puyo_tetris_combo_attack(combo: u32) -> u32 {
+    match combo {
+        0 | 1 => 0,
+        2..=4 => 1,
+        5 | 6 => 2,
+        7 | 8 => 3,
+        9..=11 => 4,
+        _ => 5,
+    }
+}
+
+fn tetr_io_combo_attack(lines: u32, combo: u32) -> u32 {
+    if lines == 0 {
+        (1.0 + 1.25 * combo as f64).ln().floor() as u32
+    } else {
+        (lines as f64 * (1.0 + 0.25 * combo as f64)).floor() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::Clear;
+
+    fn lock(lines: usize, tspin: TSpin, combo: Option<u32>, back_to_back: bool) -> LockScore {
+        LockScore {
+            clear: Clear {
+                lines,
+                tspin,
+                perfect_clear: false,
+            },
+            points: 0,
+            combo,
+            back_to_back,
+        }
+    }
+
+    #[test]
+    fn test_guideline_attack() {
+        let table = AttackTable::Guideline;
+        assert_eq!(table.attack(&lock(0, TSpin::Full, None, false)), 0);
+        assert_eq!(table.attack(&lock(1, TSpin::None, Some(0), false)), 0);
+        assert_eq!(table.attack(&lock(4, TSpin::None, Some(0), false)), 4);
+        assert_eq!(table.attack(&lock(4, TSpin::None, Some(0), true)), 5);
+        assert_eq!(table.attack(&lock(2, TSpin::Full, Some(0), true)), 5);
+        assert_eq!(table.attack(&lock(2, TSpin::Mini, Some(0), false)), 1);
+        assert_eq!(table.attack(&lock(1, TSpin::None, Some(5), false)), 2);
+        assert_eq!(table.attack(&lock(2, TSpin::None, Some(12), false)), 6);
+    }
+
+    #[test]
+    fn test_tetr_io_attack() {
+        let table = AttackTable::TetrIo;
+        assert_eq!(table.attack(&lock(4, TSpin::None, Some(0), false)), 4);
+        assert_eq!(table.attack(&lock(4, TSpin::None, Some(4), false)), 8);
+        assert_eq!(table.attack(&lock(2, TSpin::None, Some(2), false)), 1);
+        assert_eq!(table.attack(&lock(1, TSpin::None, Some(1), false)), 0);
+        assert_eq!(table.attack(&lock(1, TSpin::None, Some(6), false)), 2);
+    }
+
+    #[test]
+    fn test_puyo_puyo_tetris_attack() {
+        let table = AttackTable::PuyoPuyoTetris;
+        assert_eq!(table.attack(&lock(1, TSpin::None, Some(4), false)), 1);
+        assert_eq!(table.attack(&lock(3, TSpin::Full, Some(0), true)), 7);
+        let mut perfect_clear = lock(4, TSpin::None, Some(0), false);
+        perfect_clear.clear.perfect_clear = true;
+        assert_eq!(table.attack(&perfect_clear), 14);
+        assert_eq!(AttackTable::Guideline.attack(&perfect_clear), 4);
+    }
+}
@@ -0,0 +1,45 @@
+use rand::Rng;
+use std::env;
+use std::process;
+use tetris::net::server::{Server, ServerConfig, DEFAULT_INPUT_TIMEOUT};
+use tetris::net::MatchKind;
+use tetris::versus::VersusState;
+
+const USAGE: &str = "usage: tetris-server [address] [players] [race|battle]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let address = args.first().map_or("127.0.0.1:7777", String::as_str);
+    let players = match args
+        .get(1)
+        .map_or(Ok(2), |players| players.parse::<usize>())
+    {
+        Ok(players) if players >= 2 => players,
+        _ => exit(USAGE),
+    };
+    let kind = match args
+        .get(2)
+        .map_or(Ok(MatchKind::Battle), |kind| kind.parse())
+    {
+        Ok(kind) => kind,
+        Err(error) => exit(&error),
+    };
+    let config = ServerConfig {
+        players,
+        kind,
+        seed: rand::thread_rng().gen(),
+        input_timeout: DEFAULT_INPUT_TIMEOUT,
+    };
+    let server = Server::bind(address, config).unwrap_or_else(|error| exit(&error.to_string()));
+    println!("waiting for {} players on {}", players, address);
+    match server.run() {
+        Ok(VersusState::Won(player)) => println!("player {} won", player),
+        Ok(_) => println!("draw"),
+        Err(error) => exit(&error.to_string()),
+    }
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
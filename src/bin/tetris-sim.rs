@@ -0,0 +1,61 @@
+use std::env;
+use std::process;
+use std::str::FromStr;
+use tetris::bot::Weights;
+use tetris::sim::{simulate, SimConfig, Summary};
+
+const USAGE: &str = "usage: tetris-sim [--seeds FIRST..END] [--ruleset guideline|nes|tgm] \
+[--randomizer bag|memoryless] [--rotation srs] [--width N] [--depth N] [--previews N] \
+[--weights FILE] [--max-pieces N] [--threads N] [--format json|csv]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut config = SimConfig::default();
+    let mut csv = false;
+    for option in args.chunks(2) {
+        let [name, value] = option else {
+            exit(USAGE);
+        };
+        match name.as_str() {
+            "--seeds" => {
+                let (first, end) = value.split_once("..").unwrap_or_else(|| exit(USAGE));
+                config.seeds = parse(first)..parse(end);
+            }
+            "--ruleset" => config.ruleset = parse(value),
+            "--randomizer" => config.randomizer = parse(value),
+            "--rotation" => config.rotation = parse(value),
+            "--width" => config.bot.width = parse(value),
+            "--depth" => config.bot.depth = parse(value),
+            "--previews" => config.bot.previews = parse(value),
+            "--weights" => {
+                config.bot.weights =
+                    Weights::load(value).unwrap_or_else(|error| exit(&error.to_string()))
+            }
+            "--max-pieces" => config.max_pieces = parse(value),
+            "--threads" => config.threads = parse(value),
+            "--format" => match value.as_str() {
+                "json" => csv = false,
+                "csv" => csv = true,
+                _ => exit(USAGE),
+            },
+            _ => exit(USAGE),
+        }
+    }
+    let summary = Summary::new(&simulate(&config));
+    if csv {
+        print!("{}", summary.to_csv());
+    } else {
+        println!("{}", summary.to_json());
+    }
+}
+
+fn parse<T: FromStr>(value: &str) -> T {
+    value.parse().unwrap_or_else(|_| {
+        exit(&format!("invalid value: {}\n{}", value, USAGE));
+    })
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
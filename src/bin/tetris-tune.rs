@@ -0,0 +1,62 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use tetris::bot::Weights;
+use tetris::tuner::{Fitness, Tuner, TunerSettings};
+
+const USAGE: &str = "usage: tetris-tune [weights.json] [results.json] [generations] [lines|score]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let weights_path = args.first().map_or("weights.json", String::as_str);
+    let results_path = args.get(1).map_or("tuning-results.json", String::as_str);
+    let generations = match args
+        .get(2)
+        .map_or(Ok(TunerSettings::default().generations), |generations| {
+            generations.parse::<usize>()
+        }) {
+        Ok(generations) => generations,
+        Err(_) => exit(USAGE),
+    };
+    let fitness = match args
+        .get(3)
+        .map_or(Ok(Fitness::Lines), |fitness| fitness.parse())
+    {
+        Ok(fitness) => fitness,
+        Err(error) => exit(&error),
+    };
+    // Continue from earlier results, so retuning after a rule change starts
+    // from weights that already play well.
+    let start = if Path::new(weights_path).exists() {
+        Weights::load(weights_path).unwrap_or_else(|error| exit(&error.to_string()))
+    } else {
+        Weights::default()
+    };
+    let settings = TunerSettings {
+        generations,
+        fitness,
+        ..TunerSettings::default()
+    };
+    let mut tuner = Tuner::new(settings, start);
+    for _ in 0..generations {
+        let generation = tuner.step();
+        println!(
+            "generation {}: best {:.1}, mean {:.1}",
+            generation.generation, generation.best_fitness, generation.mean_fitness
+        );
+    }
+    let results = tuner.results();
+    println!("final games: best {:.1}", results.best_fitness);
+    results
+        .best
+        .save(weights_path)
+        .unwrap_or_else(|error| exit(&error.to_string()));
+    fs::write(results_path, results.to_json()).unwrap_or_else(|error| exit(&error.to_string()));
+    println!("best weights written to {}", weights_path);
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
@@ -1,5 +1,5 @@
 use crate::position::Position;
-use crate::tetromino::{Shape, Tetromino};
+use crate::tetromino::{all_shapes, Shape, Tetromino};
 
 const fn board_width() -> usize {
     10
@@ -12,30 +12,32 @@ pub type BoardPosition = [usize; 2];
 
 pub type Line = [BoardContent; board_width()];
 
+#[derive(Clone)]
 pub struct Board {
     lines: [Line; board_height()],
 }
 
 impl Board {
-    fn height(&self) -> usize {
+    pub fn height(&self) -> usize {
         self.lines.len()
     }
-    fn width(&self) -> usize {
+    pub fn width(&self) -> usize {
         self.lines[0].len()
     }
 
-    fn get(&self, pos: Position) -> BoardContent {
+    /// The content of a cell. Cells outside the board are `Blocked`.
+    pub fn get(&self, pos: Position) -> BoardContent {
         match board_position(pos) {
             None => BoardContent::Blocked,
             Some(board_position) => self.lines[board_position[1]][board_position[0]],
         }
     }
 
-    fn is_free(&self, pos: Position) -> bool {
+    pub fn is_free(&self, pos: Position) -> bool {
         self.get(pos) == BoardContent::Empty
     }
 
-    fn set(&mut self, pos: Position, tetronimo: Shape) -> bool {
+    pub fn set(&mut self, pos: Position, tetronimo: Shape) -> bool {
         match board_position(pos) {
             None => false,
             Some(board_position) => {
@@ -56,10 +58,243 @@ impl Board {
     }
 
     pub fn put(&mut self, tetromino: &Tetromino) {
-        tetromino
-            .blocks()
-            .map(|p| p + tetromino.position)
-            .map(|p| self.set(p, tetromino.shape));
+        for p in tetromino.blocks().iter().map(|p| *p + tetromino.position) {
+            self.set(p, tetromino.shape);
+        }
+    }
+
+    /// Returns the indices of all full lines, from bottom to top.
+    pub fn full_lines(&self) -> Vec<usize> {
+        (0..self.height())
+            .filter(|&y| is_line_full(&self.lines[y]))
+            .collect()
+    }
+
+    /// Pushes `lines` in from the bottom, moving everything else up. The
+    /// first line ends up at the bottom. Returns false if blocks were pushed
+    /// off the top of the board. Only the first `height` lines are pushed,
+    /// and more than that also returns false.
+    pub fn push_lines(&mut self, lines: &[Line]) -> bool {
+        let count = lines.len().min(board_height());
+        let fits = lines.len() <= board_height()
+            && self.lines[board_height() - count..]
+                .iter()
+                .all(is_line_empty);
+        self.lines.copy_within(0..board_height() - count, count);
+        self.lines[..count].copy_from_slice(&lines[..count]);
+        fits
+    }
+
+    /// Pushes `count` garbage lines that all have their hole at `hole`, or
+    /// in the last column if `hole` is past it.
+    pub fn push_garbage(&mut self, count: usize, hole: usize) -> bool {
+        self.push_lines(&vec![garbage_line(hole); count])
+    }
+
+    /// Returns how many lines contain garbage.
+    pub fn garbage_lines(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|line| line.contains(&BoardContent::Garbage))
+            .count()
+    }
+
+    /// Whether removing the full lines would leave the board empty.
+    pub fn is_empty_after_clear(&self) -> bool {
+        self.lines
+            .iter()
+            .all(|line| is_line_empty(line) || is_line_full(line))
+    }
+
+    /// Number of rows up to the highest block.
+    pub fn stack_height(&self) -> usize {
+        self.lines
+            .iter()
+            .rposition(|line| !is_line_empty(line))
+            .map_or(0, |y| y + 1)
+    }
+
+    /// Number of cells that are not empty.
+    pub fn filled_cells(&self) -> usize {
+        self.lines
+            .iter()
+            .flatten()
+            .filter(|content| **content != BoardContent::Empty)
+            .count()
+    }
+
+    /// Number of empty cells with a block somewhere above them.
+    pub fn holes(&self) -> usize {
+        (0..board_width())
+            .map(|x| {
+                let column = self.lines.iter().map(|line| line[x]);
+                match column
+                    .clone()
+                    .rposition(|content| content != BoardContent::Empty)
+                {
+                    Some(top) => column
+                        .take(top)
+                        .filter(|content| *content == BoardContent::Empty)
+                        .count(),
+                    None => 0,
+                }
+            })
+            .sum()
+    }
+
+    /// Index of the highest row with a block, if any.
+    pub fn highest_filled_row(&self) -> Option<usize> {
+        self.stack_height().checked_sub(1)
+    }
+
+    /// The height of every column up to its highest block.
+    pub fn column_heights(&self) -> Vec<usize> {
+        (0..board_width())
+            .map(|x| {
+                self.lines
+                    .iter()
+                    .rposition(|line| line[x] != BoardContent::Empty)
+                    .map_or(0, |y| y + 1)
+            })
+            .collect()
+    }
+
+    /// Number of blocks with a hole somewhere below them.
+    pub fn covered_cells(&self) -> usize {
+        (0..board_width())
+            .map(|x| {
+                let column = self.lines.iter().map(|line| line[x]);
+                match column
+                    .clone()
+                    .position(|content| content == BoardContent::Empty)
+                {
+                    Some(hole) => column
+                        .skip(hole)
+                        .filter(|content| *content != BoardContent::Empty)
+                        .count(),
+                    None => 0,
+                }
+            })
+            .sum()
+    }
+
+    /// How far every column lies below the lower of its neighbours. The
+    /// walls count as infinitely high.
+    pub fn well_depths(&self) -> Vec<usize> {
+        let heights = self.column_heights();
+        (0..heights.len())
+            .map(|x| {
+                let left = if x == 0 { usize::MAX } else { heights[x - 1] };
+                let right = heights.get(x + 1).copied().unwrap_or(usize::MAX);
+                left.min(right).saturating_sub(heights[x])
+            })
+            .collect()
+    }
+
+    /// Sum of the height differences between neighbouring columns.
+    pub fn bumpiness(&self) -> usize {
+        self.column_heights()
+            .windows(2)
+            .map(|pair| pair[0].abs_diff(pair[1]))
+            .sum()
+    }
+
+    /// Number of times neighbouring cells in a row change between empty and
+    /// filled, up to the highest block. The walls count as filled.
+    pub fn row_transitions(&self) -> usize {
+        self.lines[..self.stack_height()]
+            .iter()
+            .map(|line| {
+                let filled = |x: usize| line.get(x).is_none_or(|c| *c != BoardContent::Empty);
+                (0..=board_width())
+                    .filter(|&x| filled(x) != (x == 0 || filled(x - 1)))
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Number of times neighbouring cells in a column change between empty
+    /// and filled. The floor counts as filled.
+    pub fn column_transitions(&self) -> usize {
+        (0..board_width())
+            .map(|x| {
+                let mut filled = true;
+                let mut transitions = 0;
+                for line in self.lines.iter() {
+                    let next = line[x] != BoardContent::Empty;
+                    if next != filled {
+                        transitions += 1;
+                    }
+                    filled = next;
+                }
+                transitions
+            })
+            .sum()
+    }
+
+    /// Counts slots where a T pointing down would clear two lines with a
+    /// T-spin: a one cell gap under a three cell gap, covered on one side.
+    pub fn tspin_slots(&self) -> usize {
+        let heights = self.column_heights();
+        let width = board_width() as i32;
+        let free = |x: i32, y: i32| self.is_free(Position::new(x, y));
+        let gaps = |y: i32| (0..width).filter(|&x| free(x, y)).count();
+        (1..width - 1)
+            .filter(|&x| {
+                let y = heights[x as usize] as i32;
+                free(x, y)
+                    && free(x - 1, y + 1)
+                    && free(x + 1, y + 1)
+                    && !free(x - 1, y)
+                    && !free(x + 1, y)
+                    && (!free(x - 1, y + 2) || !free(x + 1, y + 2))
+                    && gaps(y) == 1
+                    && gaps(y + 1) == 3
+            })
+            .count()
+    }
+
+    pub fn has_tspin_slot(&self) -> bool {
+        self.tspin_slots() > 0
+    }
+
+    /// How many rows every piece can fall after spawning. Zero means a
+    /// spike reaches the spawn position, so the next piece may top out
+    /// without falling.
+    pub fn spawn_clearance(&self) -> usize {
+        all_shapes()
+            .into_iter()
+            .map(|shape| {
+                let mut piece = Tetromino::spawn(shape);
+                let mut rows = 0;
+                if !self.can_put(&piece) {
+                    return 0;
+                }
+                while self.can_put(&piece.get_moved_down()) {
+                    piece = piece.get_moved_down();
+                    rows += 1;
+                }
+                rows
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// FNV-1a hash of the board contents. Stable across platforms and runs,
+    /// so it can be compared between machines.
+    pub fn hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for content in self.lines.iter().flatten() {
+            let value = match content {
+                BoardContent::Empty => 0,
+                BoardContent::Blocked => 1,
+                BoardContent::Garbage => 2,
+                BoardContent::Tetromino(shape) => 3 + *shape as u8,
+            };
+            hash ^= value as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        hash
     }
 
     pub fn remove_full_lines(&mut self) -> usize {
@@ -86,12 +321,21 @@ pub enum BoardContent {
     Empty,
     Blocked,
     Tetromino(Shape),
+    Garbage,
 }
 
 fn empty_line() -> Line {
     [BoardContent::Empty; board_width()]
 }
 
+/// A line of garbage with a single hole. A `hole` past the last column is
+/// put in the last column.
+pub fn garbage_line(hole: usize) -> Line {
+    let mut line = [BoardContent::Garbage; board_width()];
+    line[hole.min(board_width() - 1)] = BoardContent::Empty;
+    line
+}
+
 fn is_line_full(line: &Line) -> bool {
     for content in line.iter() {
         if content == &BoardContent::Empty {
@@ -125,9 +369,7 @@ fn check_array_bounds(index: i32, max: usize) -> bool {
 }
 
 fn board_position(pos: Position) -> Option<BoardPosition> {
-    if !check_array_bounds(pos.x, board_width()) {
-        None
-    } else if !check_array_bounds(pos.y, board_height()) {
+    if !check_array_bounds(pos.x, board_width()) || !check_array_bounds(pos.y, board_height()) {
         None
     } else {
         Some([pos.x as usize, pos.y as usize])
@@ -135,9 +377,22 @@ fn board_position(pos: Position) -> Option<BoardPosition> {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    /// Fills the bottom `rows` rows except for `gaps`.
+    pub(crate) fn board_with_gaps(rows: i32, gaps: &[[i32; 2]]) -> Board {
+        let mut board = empty_board();
+        for y in 0..rows {
+            for x in 0..board_width() as i32 {
+                if !gaps.contains(&[x, y]) {
+                    board.set(Position::new(x, y), Shape::J);
+                }
+            }
+        }
+        board
+    }
+
     #[test]
     fn test_is_empty() {
         let board: Board = empty_board();
@@ -278,6 +533,7 @@ mod tests {
         fill_line(&mut board, 6);
         fill_line_partly(&mut board, 7);
 
+        assert_eq!(board.full_lines(), vec![0, 2, 3, 6]);
         let num_removed = board.remove_full_lines();
         assert_eq!(num_removed, 4);
         for line in &board.lines {
@@ -285,4 +541,138 @@ mod tests {
         }
         assert!(is_line_empty(&board.lines[4]));
     }
+
+    #[test]
+    fn test_push_garbage() {
+        let mut board = empty_board();
+        board.set(Position::new(0, 0), Shape::T);
+        assert!(board.push_garbage(1, 3));
+        assert!(board.push_garbage(2, 5));
+        assert_eq!(board.garbage_lines(), 3);
+        assert_eq!(board.get(Position::new(5, 0)), BoardContent::Empty);
+        assert_eq!(board.get(Position::new(5, 1)), BoardContent::Empty);
+        assert_eq!(board.get(Position::new(3, 0)), BoardContent::Garbage);
+        assert_eq!(board.get(Position::new(3, 2)), BoardContent::Empty);
+        assert_eq!(
+            board.get(Position::new(0, 3)),
+            BoardContent::Tetromino(Shape::T)
+        );
+
+        board.set(Position::new(0, board_height() as i32 - 2), Shape::T);
+        assert!(board.push_garbage(1, 0));
+        assert!(!board.push_garbage(1, 0));
+        assert_eq!(board.garbage_lines(), 5);
+    }
+
+    #[test]
+    fn test_stack_height_and_holes() {
+        let mut board = empty_board();
+        assert_eq!(board.stack_height(), 0);
+        assert_eq!(board.holes(), 0);
+        board.set(Position::new(2, 0), Shape::T);
+        board.set(Position::new(3, 3), Shape::T);
+        board.set(Position::new(3, 1), Shape::T);
+        assert_eq!(board.stack_height(), 4);
+        assert_eq!(board.holes(), 2);
+        board.push_garbage(1, 3);
+        assert_eq!(board.holes(), 3);
+    }
+
+    #[test]
+    fn test_analysis() {
+        let mut board = empty_board();
+        assert_eq!(board.highest_filled_row(), None);
+        assert_eq!(board.row_transitions(), 0);
+        assert_eq!(board.column_transitions(), 10);
+        for (x, height) in [2, 2, 2, 3, 0, 1, 2, 2, 2, 2].into_iter().enumerate() {
+            for y in 0..height {
+                board.set(Position::new(x as i32, y), Shape::J);
+            }
+        }
+        assert_eq!(board.highest_filled_row(), Some(2));
+        assert_eq!(board.column_heights(), vec![2, 2, 2, 3, 0, 1, 2, 2, 2, 2]);
+        assert_eq!(board.bumpiness(), 1 + 3 + 1 + 1);
+        assert_eq!(board.well_depths()[4], 1);
+        assert_eq!(board.well_depths()[0], 0);
+        // Row 0 has a gap at 4, row 1 gaps at 4 and 5, row 2 is empty but
+        // for column 3.
+        assert_eq!(board.row_transitions(), 2 + 2 + 4);
+        assert_eq!(board.column_transitions(), 10);
+        assert!(!board.has_tspin_slot());
+
+        board.set(Position::new(5, 2), Shape::J);
+        board.set(Position::new(3, 3), Shape::J);
+        assert_eq!(board.holes(), 1);
+        assert_eq!(board.covered_cells(), 1);
+        assert_eq!(board.tspin_slots(), 0);
+        board.set(Position::new(5, 1), Shape::J);
+        board.set(Position::new(5, 2), Shape::J);
+        board.set(Position::new(4, 0), Shape::J);
+        assert_eq!(board.holes(), 0);
+    }
+
+    #[test]
+    fn test_tspin_slot() {
+        let mut board = empty_board();
+        for y in 0..2 {
+            for x in 0..10 {
+                if !(x == 4 || y == 1 && (3..=5).contains(&x)) {
+                    board.set(Position::new(x, y), Shape::J);
+                }
+            }
+        }
+        board.set(Position::new(3, 2), Shape::J);
+        assert_eq!(board.tspin_slots(), 1);
+        assert!(board.has_tspin_slot());
+    }
+
+    #[test]
+    fn test_spawn_clearance() {
+        let mut board = empty_board();
+        assert_eq!(board.spawn_clearance(), 22);
+        for y in 0..18 {
+            board.set(Position::new(4, y), Shape::I);
+        }
+        assert_eq!(board.spawn_clearance(), 4);
+        board.set(Position::new(9, 21), Shape::I);
+        assert_eq!(board.spawn_clearance(), 4);
+        board.set(Position::new(4, 21), Shape::I);
+        assert_eq!(board.spawn_clearance(), 0);
+    }
+
+    #[test]
+    fn test_hash() {
+        let mut board = empty_board();
+        let empty_hash = board.hash();
+        assert_eq!(empty_hash, empty_board().hash());
+        board.set(Position::new(0, 0), Shape::I);
+        assert_ne!(board.hash(), empty_hash);
+        let i_hash = board.hash();
+        board.set(Position::new(0, 0), Shape::O);
+        assert_ne!(board.hash(), i_hash);
+    }
+
+    #[test]
+    fn test_push_lines() {
+        let mut board = empty_board();
+        let mut pattern = garbage_line(0);
+        pattern[9] = BoardContent::Empty;
+        assert!(board.push_lines(&[garbage_line(4), pattern]));
+        assert_eq!(board.lines[0], garbage_line(4));
+        assert_eq!(board.lines[1], pattern);
+        assert!(is_line_empty(&board.lines[2]));
+
+        let mut board = empty_board();
+        assert!(!board.push_lines(&vec![garbage_line(0); board_height() + 1]));
+        assert_eq!(board.garbage_lines(), board_height());
+    }
+
+    #[test]
+    fn test_garbage_hole_past_the_wall() {
+        assert_eq!(garbage_line(board_width()), garbage_line(board_width() - 1));
+        let mut board = empty_board();
+        assert!(board.push_garbage(2, 99));
+        assert!(board.is_free(Position::new(board_width() as i32 - 1, 1)));
+        assert_eq!(board.holes(), 0);
+    }
 }
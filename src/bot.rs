@@ -0,0 +1,574 @@
+use crate::attack::AttackTable;
+use crate::board::Board;
+use crate::game::{spawn, Game, Phase};
+use crate::input::{press, Key};
+use crate::mode::TICKS_PER_SECOND;
+use crate::movegen::{moves_from, path, Move};
+use crate::scoring::{Clear, Scoring};
+use crate::stats::DANGER_HEIGHT;
+use crate::tetromino::{Shape, Tetromino};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// How the bot values a position. Features of the board are multiplied by
+/// their weight and summed, so penalties have negative weights.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Weights {
+    /// Per row of the summed column heights.
+    pub height: f64,
+    /// Per row of the highest column above `DANGER_HEIGHT`.
+    pub danger: f64,
+    pub holes: f64,
+    /// Per row of height difference between neighbouring columns.
+    pub bumpiness: f64,
+    /// Per row of the deepest one column wide well, up to four.
+    pub well: f64,
+    /// Per slot a T can be spun into to clear two lines.
+    pub tspin_slot: f64,
+    /// Per line of garbage sent.
+    pub attack: f64,
+    /// Per line cleared without sending garbage.
+    pub burn: f64,
+    /// Per step of a running combo.
+    pub combo: f64,
+    /// For keeping the back-to-back bonus ready.
+    pub back_to_back: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            height: -0.5,
+            danger: -5.0,
+            holes: -8.0,
+            bumpiness: -0.8,
+            well: 1.0,
+            tspin_slot: 4.0,
+            attack: 6.0,
+            burn: -2.0,
+            combo: 1.0,
+            back_to_back: 3.0,
+        }
+    }
+}
+
+impl Weights {
+    /// Reads weights written by `save`, for example by the tuner.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).expect("weights serialize to JSON");
+        fs::write(path, json + "\n")
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct BotSettings {
+    /// Positions kept after each ply of the search.
+    pub width: usize,
+    /// Pieces placed ahead, including the current one.
+    pub depth: usize,
+    /// Next pieces the bot may look at.
+    pub previews: usize,
+    pub weights: Weights,
+}
+
+impl Default for BotSettings {
+    fn default() -> Self {
+        BotSettings {
+            width: 64,
+            depth: 4,
+            previews: 5,
+            weights: Weights::default(),
+        }
+    }
+}
+
+/// A placement chosen by the bot. `held` means hold is pressed first.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct BotMove {
+    pub held: bool,
+    pub target: Move,
+}
+
+/// A position in the search tree.
+#[derive(Clone)]
+struct Node {
+    board: Board,
+    hold: Option<Shape>,
+    /// How many pieces of the current piece and the queue were used.
+    used: usize,
+    scoring: Scoring,
+    /// What placing the pieces earned so far.
+    reward: f64,
+    value: f64,
+    path: Vec<Step>,
+}
+
+/// A move on the way to a node and the state right after it.
+#[derive(Copy, Clone)]
+struct Step {
+    chosen: BotMove,
+    board_hash: u64,
+    hold: Option<Shape>,
+    used: usize,
+}
+
+/// What the game should look like after the last chosen move, so the part
+/// of the tree below it can be searched further.
+struct Expected {
+    board_hash: u64,
+    hold: Option<Shape>,
+    pieces: Vec<Shape>,
+    nodes: Vec<Node>,
+}
+
+/// Beam search over the current piece, hold and the preview. The positions
+/// below the chosen move are kept and searched one piece deeper next time.
+pub struct Bot {
+    settings: BotSettings,
+    expected: Option<Expected>,
+}
+
+impl Bot {
+    pub fn new(settings: BotSettings) -> Self {
+        Bot {
+            settings,
+            expected: None,
+        }
+    }
+
+    pub fn settings(&self) -> &BotSettings {
+        &self.settings
+    }
+
+    /// Picks a move for the current tetromino of `game`. Returns `None` if
+    /// the game is not waiting for a move or every move tops out.
+    pub fn think(&mut self, game: &Game) -> Option<BotMove> {
+        if game.is_game_over() || *game.phase() != Phase::Falling {
+            return None;
+        }
+        let current = game.current_tetromino();
+        let mut pieces = vec![current.shape];
+        pieces.extend(game.preview(self.settings.previews));
+        let reused = self.reusable(game, &pieces);
+        let search = Search {
+            settings: &self.settings,
+            attack_table: game.attack_table(),
+            pieces: &pieces,
+            hold: game.ruleset().hold,
+        };
+        let mut best = reused.and_then(|nodes| search.run(nodes));
+        if best
+            .as_ref()
+            .is_none_or(|best| !self.reachable(game, best.first.chosen))
+        {
+            let root = Node {
+                board: game.board().clone(),
+                hold: game.held_shape(),
+                used: 0,
+                scoring: game.scoring().clone(),
+                reward: 0.0,
+                value: 0.0,
+                path: Vec::new(),
+            };
+            best = search.run_from_root(root, current, game.can_hold());
+        }
+        let best = best?;
+        self.expected = Some(search.expect(best.first, best.tree));
+        Some(best.first.chosen)
+    }
+
+    /// Chooses a move and plays it, counting every key as pressed. Returns
+    /// the keys used, ending with hard drop. The path is planned before any
+    /// key is pressed, so a move that cannot be reached leaves the game as it
+    /// was.
+    pub fn play(&mut self, game: &mut Game) -> Option<Vec<Key>> {
+        let chosen = self.think(game)?;
+        let mut keys = Vec::new();
+        if chosen.held {
+            keys.push(Key::Hold);
+        }
+        keys.extend(path(game.board(), start(game, chosen)?, chosen.target)?);
+        keys.push(Key::HardDrop);
+        for key in keys.iter() {
+            if !press(game, *key) {
+                return None;
+            }
+        }
+        Some(keys)
+    }
+
+    /// The kept positions, if the game went as expected after the last move.
+    fn reusable(&mut self, game: &Game, pieces: &[Shape]) -> Option<Vec<Node>> {
+        let expected = self.expected.take()?;
+        let known = expected.pieces.len().min(pieces.len());
+        let as_expected = expected.board_hash == game.board().hash()
+            && expected.hold == game.held_shape()
+            && game.can_hold()
+            && expected.pieces[..known] == pieces[..known];
+        as_expected.then_some(expected.nodes)
+    }
+
+    fn reachable(&self, game: &Game, chosen: BotMove) -> bool {
+        start(game, chosen).is_some_and(|start| path(game.board(), start, chosen.target).is_some())
+    }
+}
+
+/// The piece the path to `chosen` starts from: the current one, or the one
+/// hold would give, freshly spawned. `None` if hold is not available.
+fn start(game: &Game, chosen: BotMove) -> Option<Tetromino> {
+    if !chosen.held {
+        return Some(game.current_tetromino());
+    }
+    if !game.can_hold() {
+        return None;
+    }
+    let shape = game
+        .held_shape()
+        .or_else(|| game.preview(1).first().copied())?;
+    Some(spawn(shape))
+}
+
+/// A piece a node can place next.
+struct Choice {
+    /// Whether hold is pressed to get the piece.
+    held: bool,
+    start: Tetromino,
+    /// What is held afterwards.
+    hold: Option<Shape>,
+    used: usize,
+}
+
+/// The first move towards the best position found, and the positions that
+/// share it.
+struct Best {
+    first: Step,
+    tree: Vec<Node>,
+}
+
+struct Search<'a> {
+    settings: &'a BotSettings,
+    attack_table: AttackTable,
+    /// The current piece followed by the preview.
+    pieces: &'a [Shape],
+    /// Whether the ruleset has hold.
+    hold: bool,
+}
+
+impl Search<'_> {
+    fn run_from_root(&self, root: Node, current: Tetromino, can_hold: bool) -> Option<Best> {
+        let mut children = Vec::new();
+        for choice in self.choices(&root, Some(current), can_hold) {
+            self.expand(&root, choice, &mut children);
+        }
+        self.run(children)
+    }
+
+    /// Searches from `layer` until the paths reach the configured depth.
+    fn run(&self, mut layer: Vec<Node>) -> Option<Best> {
+        self.prune(&mut layer);
+        while layer
+            .first()
+            .is_some_and(|node| node.path.len() < self.settings.depth)
+        {
+            let mut children = Vec::new();
+            for node in layer.iter() {
+                for choice in self.choices(node, None, self.hold) {
+                    self.expand(node, choice, &mut children);
+                }
+            }
+            self.prune(&mut children);
+            if children.is_empty() {
+                break;
+            }
+            layer = children;
+        }
+        let first = layer.first()?.path[0];
+        let tree = layer
+            .into_iter()
+            .filter(|node| node.path[0].chosen == first.chosen)
+            .collect();
+        Some(Best { first, tree })
+    }
+
+    /// The pieces `node` can place next.
+    fn choices(&self, node: &Node, current: Option<Tetromino>, can_hold: bool) -> Vec<Choice> {
+        let mut choices = Vec::new();
+        let Some(&shape) = self.pieces.get(node.used) else {
+            return choices;
+        };
+        choices.push(Choice {
+            held: false,
+            start: current.unwrap_or_else(|| spawn(shape)),
+            hold: node.hold,
+            used: node.used + 1,
+        });
+        if !can_hold {
+            return choices;
+        }
+        let from_hold = match node.hold {
+            Some(held) if held != shape => Some((held, node.used + 1)),
+            Some(_) => None,
+            None => self
+                .pieces
+                .get(node.used + 1)
+                .map(|next| (*next, node.used + 2)),
+        };
+        if let Some((piece, used)) = from_hold {
+            choices.push(Choice {
+                held: true,
+                start: spawn(piece),
+                hold: Some(shape),
+                used,
+            });
+        }
+        choices
+    }
+
+    fn expand(&self, node: &Node, choice: Choice, children: &mut Vec<Node>) {
+        let weights = &self.settings.weights;
+        for m in moves_from(&node.board, choice.start) {
+            let mut child = node.clone();
+            child.hold = choice.hold;
+            child.used = choice.used;
+            let tspin = m.tspin(&node.board);
+            child.board.put(&m.tetromino);
+            let lines = child.board.full_lines().len();
+            let clear = Clear {
+                lines,
+                tspin,
+                perfect_clear: lines > 0 && child.board.is_empty_after_clear(),
+            };
+            child.board.remove_full_lines();
+            let tops_out = self
+                .pieces
+                .get(choice.used)
+                .is_some_and(|next| !child.board.can_put(&spawn(*next)));
+            if tops_out {
+                continue;
+            }
+            let lock = child.scoring.lock(clear, 1);
+            let attack = self.attack_table.attack(&lock);
+            child.reward += weights.attack * attack as f64;
+            if attack == 0 {
+                child.reward += weights.burn * lines as f64;
+            }
+            child.reward += weights.combo * lock.combo.unwrap_or(0) as f64;
+            child.value = child.reward + evaluate(&child.board, &child.scoring, weights);
+            child.path.push(Step {
+                chosen: BotMove {
+                    held: choice.held,
+                    target: m,
+                },
+                board_hash: child.board.hash(),
+                hold: choice.hold,
+                used: choice.used,
+            });
+            children.push(child);
+        }
+    }
+
+    /// Keeps the best positions, each reached only once.
+    fn prune(&self, nodes: &mut Vec<Node>) {
+        nodes.sort_by(|a, b| b.value.total_cmp(&a.value));
+        let mut seen = HashSet::new();
+        nodes.retain(|node| seen.insert((node.board.hash(), node.hold, node.used)));
+        nodes.truncate(self.settings.width);
+    }
+
+    /// Moves the positions below `first` up by one piece, to be searched
+    /// further once `first` was played.
+    fn expect(&self, first: Step, nodes: Vec<Node>) -> Expected {
+        let nodes = nodes
+            .into_iter()
+            .filter(|node| node.path.len() > 1)
+            .map(|mut node| {
+                node.path.remove(0);
+                node.used -= first.used;
+                for step in node.path.iter_mut() {
+                    step.used -= first.used;
+                }
+                node
+            })
+            .collect();
+        Expected {
+            board_hash: first.board_hash,
+            hold: first.hold,
+            pieces: self.pieces[first.used..].to_vec(),
+            nodes,
+        }
+    }
+}
+
+/// Values the shape of the stack, without what placing it earned.
+fn evaluate(board: &Board, scoring: &Scoring, weights: &Weights) -> f64 {
+    let heights = board.column_heights();
+    let well = board.well_depths().into_iter().max().unwrap_or(0);
+    let mut value = weights.height * heights.iter().sum::<usize>() as f64
+        + weights.danger * board.stack_height().saturating_sub(DANGER_HEIGHT) as f64
+        + weights.holes * board.holes() as f64
+        + weights.bumpiness * board.bumpiness() as f64
+        + weights.well * well.min(4) as f64
+        + weights.tspin_slot * board.tspin_slots() as f64;
+    if scoring.back_to_back() {
+        value += weights.back_to_back;
+    }
+    value
+}
+
+/// Lets a bot play a game at a limited number of pieces per second. Call
+/// `tick` once per frame instead of `Game::tick`.
+pub struct BotRunner {
+    bot: Bot,
+    pieces_per_second: Option<f64>,
+    ticks_waited: u64,
+}
+
+impl BotRunner {
+    /// Without a limit the bot places a piece as soon as it spawns.
+    pub fn new(bot: Bot, pieces_per_second: Option<f64>) -> Self {
+        BotRunner {
+            bot,
+            pieces_per_second,
+            ticks_waited: 0,
+        }
+    }
+
+    pub fn bot(&self) -> &Bot {
+        &self.bot
+    }
+
+    /// Plays a piece if the bot may, then ticks the game. Returns false once
+    /// the game is over.
+    pub fn tick(&mut self, game: &mut Game) -> bool {
+        self.ticks_waited += 1;
+        let ready = self
+            .pieces_per_second
+            .is_none_or(|pps| self.ticks_waited as f64 * pps >= TICKS_PER_SECOND as f64);
+        if ready && *game.phase() == Phase::Falling && self.bot.play(game).is_some() {
+            self.ticks_waited = 0;
+        }
+        game.tick()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::empty_board;
+    use crate::board::tests::board_with_gaps;
+    use crate::ruleset::Ruleset;
+
+    #[test]
+    fn test_save_and_load_weights() {
+        let path = std::env::temp_dir().join(format!("weights-{}.json", std::process::id()));
+        let weights = Weights {
+            holes: -3.5,
+            ..Weights::default()
+        };
+        weights.save(&path).unwrap();
+        assert_eq!(Weights::load(&path).unwrap(), weights);
+        fs::write(&path, "{").unwrap();
+        assert!(Weights::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_takes_the_tetris() {
+        let board = board_with_gaps(4, &[[9, 0], [9, 1], [9, 2], [9, 3]]);
+        let settings = BotSettings::default();
+        let node = Node {
+            board,
+            hold: None,
+            used: 0,
+            scoring: Scoring::new(),
+            reward: 0.0,
+            value: 0.0,
+            path: Vec::new(),
+        };
+        let pieces = [Shape::I, Shape::O, Shape::O];
+        let search = Search {
+            settings: &settings,
+            attack_table: AttackTable::default(),
+            pieces: &pieces,
+            hold: true,
+        };
+        let best = search.run_from_root(node, spawn(Shape::I), true).unwrap();
+        assert!(!best.first.chosen.held);
+        assert_eq!(best.first.board_hash, empty_board().hash());
+    }
+
+    #[test]
+    fn test_reuses_tree() {
+        let mut game = Game::with_seed(Ruleset::default(), 7);
+        let mut bot = Bot::new(BotSettings {
+            width: 16,
+            depth: 3,
+            ..BotSettings::default()
+        });
+        bot.play(&mut game).unwrap();
+        assert!(bot.expected.as_ref().is_some_and(|e| !e.nodes.is_empty()));
+        let pieces: Vec<Shape> = std::iter::once(game.current_tetromino().shape)
+            .chain(game.preview(5))
+            .collect();
+        assert!(bot.reusable(&game, &pieces).is_some());
+    }
+
+    #[test]
+    fn test_held_move_starts_from_the_next_piece() {
+        let mut game = Game::with_seed(Ruleset::default(), 7);
+        let next = game.preview(1)[0];
+        let chosen = BotMove {
+            held: true,
+            target: Move {
+                tetromino: spawn(next),
+                last_kick: None,
+            },
+        };
+        assert_eq!(start(&game, chosen), Some(spawn(next)));
+        assert!(game.hold());
+        // Hold is used up, so a held move must not press anything.
+        assert_eq!(start(&game, chosen), None);
+        assert!(!Bot::new(BotSettings::default()).reachable(&game, chosen));
+    }
+
+    #[test]
+    fn test_survives() {
+        let mut game = Game::with_seed(Ruleset::default(), 3);
+        let bot = Bot::new(BotSettings {
+            width: 8,
+            depth: 2,
+            ..BotSettings::default()
+        });
+        let mut runner = BotRunner::new(bot, None);
+        while game.pieces_locked() < 30 {
+            assert!(runner.tick(&mut game));
+        }
+        assert!(game.lines_removed() >= 8);
+        assert!(game.board().stack_height() < DANGER_HEIGHT);
+    }
+
+    #[test]
+    fn test_pieces_per_second() {
+        let mut game = Game::with_seed(Ruleset::default(), 5);
+        game.set_gravity(0);
+        let bot = Bot::new(BotSettings {
+            width: 4,
+            depth: 1,
+            ..BotSettings::default()
+        });
+        let mut runner = BotRunner::new(bot, Some(2.0));
+        for _ in 0..TICKS_PER_SECOND * 5 {
+            runner.tick(&mut game);
+        }
+        assert_eq!(game.pieces_locked(), 10);
+    }
+}
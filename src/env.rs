@@ -0,0 +1,491 @@
+use crate::game::{spawn, Game, Phase};
+use crate::input::{press, Key};
+use crate::movegen::{moves, moves_from, path, Move};
+use crate::position::Position;
+use crate::randombag::{RandomBag, Randomizer};
+use crate::ruleset::Ruleset;
+use crate::tetromino::{all_shapes, Shape};
+use std::thread;
+
+/// Every key, and doing nothing, in the order of `Env::input_actions`.
+pub const INPUTS: [Option<Key>; 8] = [
+    None,
+    Some(Key::Left),
+    Some(Key::Right),
+    Some(Key::SoftDrop),
+    Some(Key::HardDrop),
+    Some(Key::RotateClockwise),
+    Some(Key::RotateCounterClockwise),
+    Some(Key::Hold),
+];
+
+/// What an agent does in a single step.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Action {
+    /// Presses a key, or nothing, then lets one frame pass.
+    Input(Option<Key>),
+    /// Moves the current piece, or the one from hold, to `target` and hard
+    /// drops it. Frames pass until the next piece spawns.
+    Place { hold: bool, target: Move },
+}
+
+/// How steps are rewarded. Every change is multiplied by its weight and
+/// summed, so penalties have negative weights.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Rewards {
+    /// Per line cleared.
+    pub lines: f64,
+    /// Per point scored.
+    pub score: f64,
+    /// Per hole created, and negated per hole removed.
+    pub holes: f64,
+    /// Once when topping out.
+    pub game_over: f64,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Rewards {
+            lines: 1.0,
+            score: 0.0,
+            holes: 0.0,
+            game_over: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnvConfig {
+    pub ruleset: Ruleset,
+    pub randomizer: Randomizer,
+    /// Next pieces included in observations.
+    pub previews: usize,
+    /// Episodes end after this many pieces even if not lost.
+    pub max_pieces: usize,
+    pub rewards: Rewards,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        EnvConfig {
+            ruleset: Ruleset::default(),
+            randomizer: Randomizer::default(),
+            previews: 5,
+            max_pieces: 1000,
+            rewards: Rewards::default(),
+        }
+    }
+}
+
+/// What an agent sees. Every field holds 0.0 or 1.0, and shapes are one-hot
+/// in the order of `all_shapes`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Observation {
+    /// The locked cells, row by row from the bottom.
+    pub board: Vec<f32>,
+    /// The cells of the falling piece, laid out like `board`.
+    pub piece: Vec<f32>,
+    pub current: Vec<f32>,
+    /// The next pieces. Pieces beyond the visible ones are all zero.
+    pub queue: Vec<f32>,
+    /// All zero while nothing is held.
+    pub hold: Vec<f32>,
+}
+
+impl Observation {
+    /// The length of `flatten` for observations with `previews` next pieces.
+    pub fn size(width: usize, height: usize, previews: usize) -> usize {
+        2 * width * height + (previews + 2) * all_shapes().len()
+    }
+
+    /// All fields in one array, in the order they are declared.
+    pub fn flatten(&self) -> Vec<f32> {
+        let mut flat = Vec::with_capacity(
+            self.board.len()
+                + self.piece.len()
+                + self.current.len()
+                + self.queue.len()
+                + self.hold.len(),
+        );
+        flat.extend(&self.board);
+        flat.extend(&self.piece);
+        flat.extend(&self.current);
+        flat.extend(&self.queue);
+        flat.extend(&self.hold);
+        flat
+    }
+}
+
+/// What happened during a step, for logging rather than learning.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct StepInfo {
+    pub lines: usize,
+    pub score: u32,
+    pub pieces: usize,
+    pub holes: usize,
+    /// The episode hit `max_pieces` instead of topping out.
+    pub truncated: bool,
+    /// The action was not possible, so nothing happened.
+    pub invalid: bool,
+}
+
+pub type StepResult = (Observation, f64, bool, StepInfo);
+
+/// A reinforcement learning environment playing single player games.
+pub struct Env {
+    config: EnvConfig,
+    game: Game,
+}
+
+impl Env {
+    /// The environment starts with the game of seed 0.
+    pub fn new(config: EnvConfig) -> Self {
+        let game = new_game(&config, 0);
+        Env { config, game }
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.config
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Starts a new episode with the game of `seed`.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.game = new_game(&self.config, seed);
+        self.observe()
+    }
+
+    /// Every primitive action, one per entry of `INPUTS`.
+    pub fn input_actions() -> Vec<Action> {
+        INPUTS.iter().map(|key| Action::Input(*key)).collect()
+    }
+
+    /// Every placement of the current piece and, if hold is available, of
+    /// the piece hold would give. Empty while no piece is falling.
+    pub fn placement_actions(&self) -> Vec<Action> {
+        if self.game.is_game_over() || *self.game.phase() != Phase::Falling {
+            return Vec::new();
+        }
+        let board = self.game.board();
+        let current = self.game.current_tetromino();
+        let mut actions: Vec<Action> = moves_from(board, current)
+            .into_iter()
+            .map(|target| Action::Place {
+                hold: false,
+                target,
+            })
+            .collect();
+        if let Some(shape) = self.shape_from_hold().filter(|_| self.game.can_hold()) {
+            actions.extend(
+                moves(board, shape)
+                    .into_iter()
+                    .map(|target| Action::Place { hold: true, target }),
+            );
+        }
+        actions
+    }
+
+    pub fn step(&mut self, action: Action) -> StepResult {
+        let lines = self.game.lines_removed();
+        let score = self.game.score();
+        let holes = self.game.board().holes();
+        let valid = match action {
+            Action::Input(key) => {
+                if let Some(key) = key {
+                    self.press(key);
+                }
+                self.game.tick();
+                true
+            }
+            Action::Place { hold, target } => self.place(hold, target),
+        };
+        let game_over = self.game.is_game_over();
+        let truncated = !game_over && self.game.pieces_locked() >= self.config.max_pieces;
+        let info = StepInfo {
+            lines: self.game.lines_removed(),
+            score: self.game.score(),
+            pieces: self.game.pieces_locked(),
+            holes: self.game.board().holes(),
+            truncated,
+            invalid: !valid,
+        };
+        let rewards = self.config.rewards;
+        let mut reward = rewards.lines * (info.lines - lines) as f64
+            + rewards.score * (info.score - score) as f64
+            + rewards.holes * (info.holes as f64 - holes as f64);
+        if game_over {
+            reward += rewards.game_over;
+        }
+        (self.observe(), reward, game_over || truncated, info)
+    }
+
+    /// The shape hold would give: the held one, or the next one if nothing
+    /// is held yet.
+    fn shape_from_hold(&self) -> Option<Shape> {
+        self.game
+            .held_shape()
+            .or_else(|| self.game.preview(1).first().copied())
+    }
+
+    fn press(&mut self, key: Key) {
+        press(&mut self.game, key);
+    }
+
+    fn place(&mut self, hold: bool, target: Move) -> bool {
+        if *self.game.phase() != Phase::Falling || (hold && !self.game.can_hold()) {
+            return false;
+        }
+        let start = if hold {
+            match self.shape_from_hold() {
+                // The held piece comes out where new pieces spawn.
+                Some(shape) => spawn(shape),
+                None => return false,
+            }
+        } else {
+            self.game.current_tetromino()
+        };
+        let keys = match path(self.game.board(), start, target) {
+            Some(keys) => keys,
+            None => return false,
+        };
+        if hold {
+            self.press(Key::Hold);
+        }
+        for key in keys {
+            self.press(key);
+        }
+        self.press(Key::HardDrop);
+        while !self.game.is_game_over() && *self.game.phase() != Phase::Falling {
+            self.game.tick();
+        }
+        true
+    }
+
+    pub fn observe(&self) -> Observation {
+        let board = self.game.board();
+        let (width, height) = (board.width(), board.height());
+        let cells = |filled: &dyn Fn(Position) -> bool| -> Vec<f32> {
+            (0..height as i32)
+                .flat_map(|y| (0..width as i32).map(move |x| Position::new(x, y)))
+                .map(|position| filled(position) as u8 as f32)
+                .collect()
+        };
+        let falling = *self.game.phase() == Phase::Falling && !self.game.is_game_over();
+        let piece = self.game.current_tetromino();
+        let piece_cells = piece.cells();
+        let queue = self.game.preview(self.config.previews);
+        Observation {
+            board: cells(&|position| !board.is_free(position)),
+            piece: cells(&|position| falling && piece_cells.contains(&position)),
+            current: one_hot(Some(piece.shape).filter(|_| falling)),
+            queue: (0..self.config.previews)
+                .flat_map(|index| one_hot(queue.get(index).copied()))
+                .collect(),
+            hold: one_hot(self.game.held_shape()),
+        }
+    }
+}
+
+fn new_game(config: &EnvConfig, seed: u64) -> Game {
+    Game::with_bag(
+        config.ruleset,
+        RandomBag::with_randomizer(config.randomizer, seed),
+    )
+}
+
+fn one_hot(shape: Option<Shape>) -> Vec<f32> {
+    all_shapes()
+        .into_iter()
+        .map(|other| (Some(other) == shape) as u8 as f32)
+        .collect()
+}
+
+/// Many environments stepped together, on several threads. Episodes that
+/// end are reset right away with the next unused seed, so the observation
+/// returned with `done` is the first of the new episode.
+pub struct VecEnv {
+    envs: Vec<Env>,
+    next_seed: u64,
+    threads: usize,
+}
+
+impl VecEnv {
+    pub fn new(config: EnvConfig, count: usize, threads: usize) -> Self {
+        VecEnv {
+            envs: (0..count).map(|_| Env::new(config.clone())).collect(),
+            next_seed: 0,
+            threads: threads.max(1),
+        }
+    }
+
+    pub fn envs(&self) -> &[Env] {
+        &self.envs
+    }
+
+    /// Resets every environment, the first with `seed` and each further one
+    /// with the next seed.
+    pub fn reset(&mut self, seed: u64) -> Vec<Observation> {
+        self.next_seed = seed + self.envs.len() as u64;
+        self.envs
+            .iter_mut()
+            .zip(seed..)
+            .map(|(env, seed)| env.reset(seed))
+            .collect()
+    }
+
+    /// Steps environment `i` with `actions[i]`.
+    pub fn step(&mut self, actions: &[Action]) -> Vec<StepResult> {
+        assert_eq!(actions.len(), self.envs.len(), "one action per environment");
+        let chunk = self.envs.len().div_ceil(self.threads).max(1);
+        let mut results: Vec<StepResult> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .envs
+                .chunks_mut(chunk)
+                .zip(actions.chunks(chunk))
+                .map(|(envs, actions)| {
+                    scope.spawn(move || {
+                        envs.iter_mut()
+                            .zip(actions)
+                            .map(|(env, action)| env.step(*action))
+                            .collect::<Vec<StepResult>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("environment thread panicked"))
+                .collect()
+        });
+        for (env, result) in self.envs.iter_mut().zip(results.iter_mut()) {
+            if result.2 {
+                result.0 = env.reset(self.next_seed);
+                self.next_seed += 1;
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_observation() {
+        let mut env = Env::new(EnvConfig::default());
+        let observation = env.reset(3);
+        assert_eq!(observation.flatten().len(), Observation::size(10, 24, 5));
+        assert_eq!(observation.board.iter().sum::<f32>(), 0.0);
+        assert_eq!(observation.piece.iter().sum::<f32>(), 4.0);
+        let current = env.game().current_tetromino().shape;
+        assert_eq!(observation.current, one_hot(Some(current)));
+        assert_eq!(observation.queue.iter().sum::<f32>(), 5.0);
+        assert_eq!(observation.hold.iter().sum::<f32>(), 0.0);
+    }
+
+    #[test]
+    fn test_placement_actions() {
+        let mut env = Env::new(EnvConfig::default());
+        env.reset(0);
+        let actions = env.placement_actions();
+        let board = env.game().board();
+        let current = env.game().current_tetromino().shape;
+        let next = env.game().preview(1)[0];
+        assert_eq!(
+            actions.len(),
+            moves(board, current).len() + moves(board, next).len()
+        );
+        let (observation, _, done, info) = env.step(actions[0]);
+        assert!(!done && !info.invalid);
+        assert_eq!(info.pieces, 1);
+        assert_eq!(observation.board.iter().sum::<f32>(), 4.0);
+
+        let held = *env
+            .placement_actions()
+            .iter()
+            .find(|action| matches!(action, Action::Place { hold: true, .. }))
+            .unwrap();
+        let (observation, _, _, info) = env.step(held);
+        assert!(!info.invalid);
+        assert_eq!(observation.hold.iter().sum::<f32>(), 1.0);
+        let (_, _, _, info) = env.step(held);
+        assert!(info.invalid);
+    }
+
+    #[test]
+    fn test_placements_from_moved_piece() {
+        let mut env = Env::new(EnvConfig::default());
+        env.reset(0);
+        env.step(Action::Input(Some(Key::RotateClockwise)));
+        env.step(Action::Input(Some(Key::Left)));
+        let board = env.game().board();
+        let current = env.game().current_tetromino();
+        let placements: Vec<Move> = env
+            .placement_actions()
+            .into_iter()
+            .filter_map(|action| match action {
+                Action::Place {
+                    hold: false,
+                    target,
+                } => Some(target),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(placements, moves_from(board, current));
+        assert!(placements
+            .iter()
+            .all(|target| path(board, current, *target).is_some()));
+    }
+
+    #[test]
+    fn test_inputs_until_game_over() {
+        let config = EnvConfig {
+            rewards: Rewards {
+                game_over: -10.0,
+                ..Rewards::default()
+            },
+            ..EnvConfig::default()
+        };
+        let mut env = Env::new(config);
+        env.reset(1);
+        let hard_drop = Env::input_actions()[4];
+        let (reward, done, info) = loop {
+            let (_, reward, done, info) = env.step(hard_drop);
+            if done {
+                break (reward, done, info);
+            }
+            assert!(env.game().pieces_locked() < 100);
+        };
+        assert!(done && !info.truncated);
+        assert_eq!(reward, -10.0);
+    }
+
+    #[test]
+    fn test_vec_env() {
+        let config = EnvConfig {
+            max_pieces: 1,
+            ..EnvConfig::default()
+        };
+        let mut envs = VecEnv::new(config, 3, 2);
+        let observations = envs.reset(10);
+        assert_eq!(observations.len(), 3);
+        let actions: Vec<Action> = envs
+            .envs()
+            .iter()
+            .map(|env| env.placement_actions()[0])
+            .collect();
+        let results = envs.step(&actions);
+        assert!(results
+            .iter()
+            .all(|(_, _, done, info)| *done && info.truncated));
+        assert!(envs
+            .envs()
+            .iter()
+            .all(|env| env.game().pieces_locked() == 0));
+        let mut expected = Env::new(EnvConfig::default());
+        assert_eq!(results[0].0, expected.reset(13));
+    }
+}
@@ -0,0 +1,73 @@
+use crate::scoring::TSpin;
+use crate::tetromino::{Shape, Tetromino};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::Sender;
+
+/// Something that happened in a `Game`, in the order it happened. Events
+/// are only built while they are recorded or a listener is registered.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    Spawned {
+        piece: Tetromino,
+    },
+    /// The current tetromino moved without rotating, by the player, gravity
+    /// or garbage pushing it up.
+    Moved {
+        piece: Tetromino,
+    },
+    /// `kick` is the index of the offset that made the rotation fit, zero
+    /// meaning no kick was needed.
+    Rotated {
+        piece: Tetromino,
+        kick: usize,
+    },
+    /// `tspin` is also set for T-spins that cleared no lines, which get no
+    /// `LinesCleared`.
+    Locked {
+        piece: Tetromino,
+        tspin: TSpin,
+    },
+    /// Full lines were found after a lock. `rows` are their indices from
+    /// bottom to top. With a line clear delay the lines are removed later.
+    LinesCleared {
+        rows: Vec<usize>,
+        tspin: TSpin,
+        perfect_clear: bool,
+    },
+    /// `shape` was put into hold. The piece taken out spawns next.
+    Held {
+        shape: Shape,
+    },
+    LevelUp {
+        level: u32,
+    },
+    GarbageReceived {
+        lines: usize,
+    },
+    GameOver,
+}
+
+/// Receives events from a `Game` as they happen, from inside the call that
+/// caused them.
+pub trait GameListener: Send {
+    fn on_event(&mut self, event: &GameEvent);
+}
+
+impl<F: FnMut(&GameEvent) + Send> GameListener for F {
+    fn on_event(&mut self, event: &GameEvent) {
+        self(event)
+    }
+}
+
+/// Forwards events to a channel. Events are dropped once the receiver is
+/// gone.
+impl GameListener for Sender<GameEvent> {
+    fn on_event(&mut self, event: &GameEvent) {
+        let _ = self.send(event.clone());
+    }
+}
+
+/// Identifies a registered listener, see `Game::remove_listener`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ListenerId(pub(crate) usize);
@@ -0,0 +1,368 @@
+use crate::board::Board;
+use crate::event::GameEvent;
+use crate::game::{landing_position, rotation_candidates, Direction, Game};
+use crate::input::Key;
+use crate::movegen::{placement, Placement};
+use crate::tetromino::{Shape, Tetromino};
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// An input as counted by finesse. Holding a direction until the tetromino
+/// hits the wall counts as a single input.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum FinesseInput {
+    Left,
+    Right,
+    DasLeft,
+    DasRight,
+    RotateClockwise,
+    RotateCounterClockwise,
+}
+
+const INPUTS: [FinesseInput; 6] = [
+    FinesseInput::Left,
+    FinesseInput::Right,
+    FinesseInput::DasLeft,
+    FinesseInput::DasRight,
+    FinesseInput::RotateClockwise,
+    FinesseInput::RotateCounterClockwise,
+];
+
+fn apply(board: &Board, t: Tetromino, input: FinesseInput) -> Option<Tetromino> {
+    let shift = |mut t: Tetromino, offset: [i32; 2], repeat: bool| {
+        let mut moved = false;
+        while board.can_put(&t.get_offset(offset)) {
+            t = t.get_offset(offset);
+            moved = true;
+            if !repeat {
+                break;
+            }
+        }
+        moved.then_some(t)
+    };
+    let rotate = |direction: Direction| {
+        rotation_candidates(t, direction)
+            .into_iter()
+            .find(|candidate| board.can_put(candidate))
+    };
+    match input {
+        FinesseInput::Left => shift(t, [-1, 0], false),
+        FinesseInput::Right => shift(t, [1, 0], false),
+        FinesseInput::DasLeft => shift(t, [-1, 0], true),
+        FinesseInput::DasRight => shift(t, [1, 0], true),
+        FinesseInput::RotateClockwise => rotate(Direction::Clockwise),
+        FinesseInput::RotateCounterClockwise => rotate(Direction::CounterClockwise),
+    }
+}
+
+/// Finds the shortest input sequence from `start` to every placement that
+/// can be reached without soft dropping. The hard drop is not included.
+pub fn finesse_table(board: &Board, start: Tetromino) -> HashMap<Placement, Vec<FinesseInput>> {
+    let mut paths: HashMap<Tetromino, Vec<FinesseInput>> = HashMap::new();
+    let mut table = HashMap::new();
+    let mut queue = VecDeque::new();
+    if !board.can_put(&start) {
+        return table;
+    }
+    paths.insert(start, Vec::new());
+    queue.push_back(start);
+    while let Some(t) = queue.pop_front() {
+        let path = paths[&t].clone();
+        table
+            .entry(placement(landing_position(board, t)))
+            .or_insert_with(|| path.clone());
+        for input in INPUTS {
+            if let Some(next) = apply(board, t, input) {
+                paths.entry(next).or_insert_with(|| {
+                    queue.push_back(next);
+                    let mut next_path = path.clone();
+                    next_path.push(input);
+                    next_path
+                });
+            }
+        }
+    }
+    table
+}
+
+/// How a single piece was placed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PieceFinesse {
+    pub shape: Shape,
+    /// Movement and rotation keys pressed for the piece.
+    pub inputs: usize,
+    /// A shortest way to the same placement.
+    pub optimal: Vec<FinesseInput>,
+    pub faults: usize,
+}
+
+/// Key presses and game events, in the order they happened.
+enum Record {
+    Key(Key),
+    Event(GameEvent),
+}
+
+/// The piece being judged.
+struct Current {
+    /// Where the piece was once it spawned, after any initial rotation.
+    start: Tetromino,
+    inputs: usize,
+    judged: bool,
+    /// Whether a key was pressed or the piece moved since it spawned.
+    settled: bool,
+}
+
+/// Judges the finesse of every piece of a game. Pieces that were held or
+/// soft dropped, and placements that can't be reached without soft drop,
+/// are not judged. Neither is the piece falling when the analyser is
+/// created, nor pieces locked after garbage arrived until the next
+/// `update`.
+pub struct FinesseAnalyser {
+    sender: Sender<Record>,
+    records: Receiver<Record>,
+    /// The board as the next piece will see it.
+    board: Board,
+    /// Whether `board` still matches the game's.
+    synced: bool,
+    current: Option<Current>,
+    /// Set when the next piece must not be judged.
+    unjudged_next: bool,
+    pieces: Vec<PieceFinesse>,
+}
+
+impl FinesseAnalyser {
+    /// Starts judging the pieces of `game` by listening to its events.
+    pub fn new(game: &mut Game) -> Self {
+        let (sender, records) = mpsc::channel();
+        let events = sender.clone();
+        game.add_listener(move |event: &GameEvent| {
+            let _ = events.send(Record::Event(event.clone()));
+        });
+        FinesseAnalyser {
+            sender,
+            records,
+            board: game.board().clone(),
+            synced: true,
+            current: None,
+            unjudged_next: false,
+            pieces: Vec::new(),
+        }
+    }
+
+    /// Counts a key press. Call it before the key is applied to the game.
+    pub fn key_down(&mut self, key: Key) {
+        let _ = self.sender.send(Record::Key(key));
+    }
+
+    /// Call once per frame after the game ticked. Returns how the pieces
+    /// locked since the last call were placed.
+    pub fn update(&mut self, game: &Game) -> Vec<PieceFinesse> {
+        let judged = self.pieces.len();
+        while let Ok(record) = self.records.try_recv() {
+            match record {
+                Record::Key(key) => self.record_key(key),
+                Record::Event(event) => self.record_event(event),
+            }
+        }
+        self.board = game.board().clone();
+        self.board.remove_full_lines();
+        self.synced = true;
+        self.pieces[judged..].to_vec()
+    }
+
+    fn record_key(&mut self, key: Key) {
+        let current = match &mut self.current {
+            Some(current) => current,
+            // Keys pressed between pieces only buffer initial rotations and
+            // holds, which the next piece starts from.
+            None => {
+                if key == Key::SoftDrop {
+                    self.unjudged_next = true;
+                }
+                return;
+            }
+        };
+        current.settled = true;
+        match key {
+            Key::Left | Key::Right | Key::RotateClockwise | Key::RotateCounterClockwise => {
+                current.inputs += 1;
+            }
+            Key::SoftDrop | Key::Hold => current.judged = false,
+            Key::HardDrop => (),
+        }
+    }
+
+    fn record_event(&mut self, event: GameEvent) {
+        match event {
+            GameEvent::Spawned { piece } => {
+                self.current = Some(Current {
+                    start: piece,
+                    inputs: 0,
+                    judged: !std::mem::take(&mut self.unjudged_next),
+                    settled: false,
+                });
+            }
+            GameEvent::Rotated { piece, .. } => {
+                if let Some(current) = self.current.as_mut().filter(|current| !current.settled) {
+                    current.start = piece;
+                }
+            }
+            GameEvent::Moved { .. } => {
+                if let Some(current) = &mut self.current {
+                    current.settled = true;
+                }
+            }
+            // A hold while falling. Initial holds happen between pieces.
+            GameEvent::Held { .. } if self.current.is_some() => {
+                self.current = None;
+                self.unjudged_next = true;
+            }
+            GameEvent::Locked { piece, .. } => {
+                if let Some(current) = self.current.take() {
+                    if current.judged && self.synced {
+                        self.judge(current, piece);
+                    }
+                }
+                self.board.put(&piece);
+            }
+            GameEvent::LinesCleared { .. } => {
+                self.board.remove_full_lines();
+            }
+            GameEvent::GarbageReceived { .. } => self.synced = false,
+            _ => (),
+        }
+    }
+
+    fn judge(&mut self, current: Current, locked: Tetromino) {
+        let optimal = match finesse_table(&self.board, current.start).remove(&placement(locked)) {
+            Some(optimal) => optimal,
+            None => return,
+        };
+        self.pieces.push(PieceFinesse {
+            shape: locked.shape,
+            inputs: current.inputs,
+            faults: current.inputs.saturating_sub(optimal.len()),
+            optimal,
+        });
+    }
+
+    pub fn pieces(&self) -> &[PieceFinesse] {
+        &self.pieces
+    }
+
+    pub fn faults(&self) -> usize {
+        self.pieces.iter().map(|piece| piece.faults).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::empty_board;
+    use crate::game::{spawn, Phase};
+    use crate::tetromino::Orientation;
+
+    fn optimal_lengths(shape: Shape) -> Vec<usize> {
+        let table = finesse_table(&empty_board(), spawn(shape));
+        let mut lengths: Vec<usize> = table.values().map(|path| path.len()).collect();
+        lengths.sort();
+        lengths
+    }
+
+    #[test]
+    fn test_o_placements() {
+        assert_eq!(optimal_lengths(Shape::O), vec![0, 1, 1, 1, 1, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_every_placement_takes_at_most_four_inputs() {
+        for shape in [Shape::I, Shape::J, Shape::L, Shape::S, Shape::T, Shape::Z] {
+            let lengths = optimal_lengths(shape);
+            assert_eq!(lengths[0], 0);
+            assert!(*lengths.last().unwrap() <= 4, "{:?}", shape);
+        }
+        assert_eq!(optimal_lengths(Shape::T).len(), 34);
+        assert_eq!(optimal_lengths(Shape::I).len(), 17);
+    }
+
+    #[test]
+    fn test_vertical_i_at_wall() {
+        let table = finesse_table(&empty_board(), spawn(Shape::I));
+        let mut target = spawn(Shape::I);
+        target.orientation = Orientation::East;
+        while empty_board().can_put(&target.get_moved_left()) {
+            target = target.get_moved_left();
+        }
+        let path = &table[&placement(landing_position(&empty_board(), target))];
+        assert_eq!(path.len(), 2);
+    }
+
+    #[test]
+    fn test_analyser() {
+        let mut game = Game::new();
+        let mut analyser = FinesseAnalyser::new(&mut game);
+        game.spawn_specific(Shape::O);
+        for _ in 0..3 {
+            analyser.key_down(Key::Left);
+            game.move_left();
+        }
+        analyser.key_down(Key::HardDrop);
+        game.hard_drop();
+        let pieces = analyser.update(&game);
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(pieces[0].inputs, 3);
+        assert_eq!(pieces[0].optimal.len(), 2);
+        assert_eq!(pieces[0].faults, 1);
+
+        game.spawn_specific(Shape::T);
+        analyser.key_down(Key::Hold);
+        game.hold();
+        analyser.key_down(Key::HardDrop);
+        game.hard_drop();
+        assert_eq!(analyser.update(&game), []);
+
+        analyser.key_down(Key::HardDrop);
+        game.hard_drop();
+        assert_eq!(analyser.update(&game)[0].faults, 0);
+        assert_eq!(analyser.faults(), 1);
+        assert_eq!(analyser.pieces().len(), 2);
+    }
+
+    #[test]
+    fn test_several_locks_per_update() {
+        let mut game = Game::new();
+        let mut analyser = FinesseAnalyser::new(&mut game);
+        for _ in 0..3 {
+            game.spawn_specific(Shape::O);
+            analyser.key_down(Key::Left);
+            game.move_left();
+            analyser.key_down(Key::HardDrop);
+            game.hard_drop();
+        }
+        let pieces = analyser.update(&game);
+        assert_eq!(pieces.len(), 3);
+        assert!(pieces.iter().all(|piece| piece.faults == 0));
+    }
+
+    #[test]
+    fn test_initial_rotation_is_the_start() {
+        let mut game = Game::new();
+        let mut analyser = FinesseAnalyser::new(&mut game);
+        game.spawn_specific(Shape::T);
+        game.buffer_rotation(Some(Direction::Clockwise));
+        analyser.key_down(Key::HardDrop);
+        game.hard_drop();
+        while *game.phase() != Phase::Falling {
+            game.tick();
+        }
+        let spawned = game.current_tetromino();
+        assert_eq!(spawned.orientation, Orientation::East);
+        analyser.key_down(Key::HardDrop);
+        game.hard_drop();
+        let pieces = analyser.update(&game);
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[1].shape, spawned.shape);
+        assert_eq!(pieces[1].inputs, 0);
+        assert!(pieces[1].optimal.is_empty());
+    }
+}
@@ -1,121 +1,698 @@
-use crate::board::{empty_board, Board};
-use crate::position::Position;
+use crate::attack::AttackTable;
+use crate::board::{empty_board, Board, Line};
+use crate::event::{GameEvent, GameListener, ListenerId};
 use crate::randombag::RandomBag;
+use crate::ruleset::Ruleset;
+use crate::scoring::{Clear, LockScore, Scoring, TSpin};
 use crate::tetromino::{Orientation, Shape, Tetromino};
+use std::cell::Cell;
 
-struct Game {
+pub struct Game {
     board: Board,
     current_tetromino: Tetromino,
     random_bag: RandomBag,
     lines_removed: usize,
+    pieces_locked: usize,
+    key_presses: usize,
+    ghost: Cell<Option<Tetromino>>,
+    soft_drop: bool,
+    soft_drop_factor: SoftDropFactor,
+    ruleset: Ruleset,
+    held_shape: Option<Shape>,
+    hold_used: bool,
+    buffered_rotation: Option<Direction>,
+    buffered_hold: bool,
+    phase: Phase,
+    gravity: u32,
+    gravity_progress: u32,
+    level: u32,
+    scoring: Scoring,
+    last_rotation_kick: Option<usize>,
+    last_lock: Option<LockScore>,
+    last_locked_tetromino: Option<Tetromino>,
+    attack_table: AttackTable,
+    attack_sent: u32,
+    game_over: Option<TopOut>,
+    events: Option<Vec<GameEvent>>,
+    listeners: Vec<(ListenerId, Box<dyn GameListener>)>,
+    next_listener_id: usize,
 }
 
-enum Direction {
+/// Gravity is measured in 1/GRAVITY_UNIT rows per tick.
+pub const GRAVITY_UNIT: u32 = 65536;
+
+/// Why a game ended.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TopOut {
+    /// A new tetromino overlapped the stack as it spawned.
+    BlockOut,
+    /// The falling tetromino overlapped the stack when it locked.
+    LockOut,
+    /// Garbage pushed blocks off the top of the board, or left no room for
+    /// the falling tetromino.
+    Garbage,
+}
+
+/// The state of the game between two ticks.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Phase {
+    /// The current tetromino is falling and can be moved.
+    Falling,
+    /// Full lines are shown before they are removed. `rows` are the indices
+    /// of the lines being cleared, from bottom to top.
+    LineClear { rows: Vec<usize>, ticks_left: u32 },
+    /// Waiting for the next tetromino to spawn.
+    Entry { ticks_left: u32 },
+}
+
+/// How fast the current tetromino falls while soft drop is held.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum SoftDropFactor {
+    /// Falls this many times faster than gravity.
+    Multiplier(usize),
+    /// Falls to the floor immediately but does not lock ("sonic drop").
+    Infinite,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Direction {
     Clockwise,
     CounterClockwise,
 }
 
 impl Game {
     pub fn new() -> Self {
+        Self::with_ruleset(Ruleset::default())
+    }
+
+    pub fn with_ruleset(ruleset: Ruleset) -> Self {
+        Self::with_bag(ruleset, RandomBag::new())
+    }
+
+    /// Creates a game whose piece sequence is determined by `seed`.
+    pub fn with_seed(ruleset: Ruleset, seed: u64) -> Self {
+        Self::with_bag(ruleset, RandomBag::with_seed(seed))
+    }
+
+    /// Creates a game whose pieces, the first one included, come from
+    /// `random_bag`.
+    pub fn with_bag(ruleset: Ruleset, mut random_bag: RandomBag) -> Self {
+        let first = random_bag.get();
         Game {
             board: empty_board(),
-            current_tetromino: spawn(Shape::I),
-            random_bag: RandomBag::new(),
+            current_tetromino: spawn(first),
+            random_bag,
             lines_removed: 0,
+            pieces_locked: 0,
+            key_presses: 0,
+            ghost: Cell::new(None),
+            soft_drop: false,
+            soft_drop_factor: SoftDropFactor::Multiplier(20),
+            ruleset,
+            held_shape: None,
+            hold_used: false,
+            buffered_rotation: None,
+            buffered_hold: false,
+            phase: Phase::Falling,
+            gravity: GRAVITY_UNIT,
+            gravity_progress: 0,
+            level: 1,
+            scoring: Scoring::new(),
+            last_rotation_kick: None,
+            last_lock: None,
+            last_locked_tetromino: None,
+            attack_table: AttackTable::default(),
+            attack_sent: 0,
+            game_over: None,
+            events: None,
+            listeners: Vec::new(),
+            next_listener_id: 0,
         }
     }
 
-    fn spawn_specific(&mut self, shape: Shape) {
-        self.current_tetromino = spawn(shape);
+    pub(crate) fn spawn_specific(&mut self, shape: Shape) {
+        self.phase = Phase::Falling;
+        self.gravity_progress = 0;
+        self.last_rotation_kick = None;
+        self.set_current_tetromino(spawn(shape));
+        let piece = self.current_tetromino;
+        self.emit(|| GameEvent::Spawned { piece });
+        if !self.board.can_put(&self.current_tetromino) {
+            self.top_out(TopOut::BlockOut);
+        }
     }
 
     fn spawn(&mut self) {
-        let shape = self.random_bag.get();
+        let mut shape = self.random_bag.get();
+        self.hold_used = false;
+        if self.ruleset.hold && self.ruleset.initial_hold && self.buffered_hold {
+            self.buffered_hold = false;
+            self.emit(|| GameEvent::Held { shape });
+            shape = self.swap_hold(shape);
+            self.hold_used = true;
+        }
         self.spawn_specific(shape);
+        if self.ruleset.initial_rotation {
+            if let Some(direction) = self.buffered_rotation {
+                self.rotate(direction);
+            }
+        }
     }
 
-    fn put_current_tetromino(&mut self) -> bool {
+    /// Buffers a rotation to be applied to the next tetromino as it spawns,
+    /// if the ruleset has an Initial Rotation System.
+    pub fn buffer_rotation(&mut self, direction: Option<Direction>) {
+        self.buffered_rotation = direction;
+    }
+
+    /// Buffers a hold to be applied to the next tetromino as it spawns, if
+    /// the ruleset has an Initial Hold System. The buffer is cleared once a
+    /// spawn uses it.
+    pub fn buffer_hold(&mut self, active: bool) {
+        self.buffered_hold = active;
+    }
+
+    /// Swaps the current tetromino with the held one, or with the next one
+    /// from the bag if nothing is held yet. Only allowed once per piece.
+    pub fn hold(&mut self) -> bool {
+        if !self.ruleset.hold
+            || self.hold_used
+            || self.phase != Phase::Falling
+            || self.game_over.is_some()
+        {
+            return false;
+        }
+        let held = self.current_tetromino.shape;
+        self.emit(|| GameEvent::Held { shape: held });
+        let shape = self.swap_hold(held);
+        self.spawn_specific(shape);
+        self.hold_used = true;
+        true
+    }
+
+    fn swap_hold(&mut self, shape: Shape) -> Shape {
+        match self.held_shape.replace(shape) {
+            Some(held) => held,
+            None => self.random_bag.get(),
+        }
+    }
+
+    pub fn put_current_tetromino(&mut self) -> bool {
         if self.board.can_put(&self.current_tetromino) {
             self.board.put(&self.current_tetromino);
+            self.invalidate_ghost();
+            let piece = self.current_tetromino;
+            self.emit(|| GameEvent::Locked {
+                piece,
+                tspin: TSpin::None,
+            });
             self.spawn();
             return true;
         }
         false
     }
 
-    fn move_down(&mut self) -> bool {
+    pub fn move_down(&mut self) -> bool {
         self.update_tetromino(vec![self.current_tetromino.get_moved_down()])
     }
 
-    fn move_left(&mut self) -> bool {
+    pub fn move_left(&mut self) -> bool {
         self.update_tetromino(vec![self.current_tetromino.get_moved_left()])
     }
 
-    fn move_right(&mut self) -> bool {
+    pub fn move_right(&mut self) -> bool {
         self.update_tetromino(vec![self.current_tetromino.get_moved_right()])
     }
 
-    fn drop(&mut self) {
-        while self.move_down() {}
+    /// Moves the current tetromino to the floor without locking it and
+    /// returns the number of rows it fell.
+    pub fn sonic_drop(&mut self) -> usize {
+        if self.phase != Phase::Falling {
+            return 0;
+        }
+        let rows = (self.current_tetromino.position.y - self.ghost().position.y) as usize;
+        if rows > 0 {
+            self.last_rotation_kick = None;
+            self.set_current_tetromino(self.ghost());
+            self.emit_moved();
+        }
+        rows
+    }
+
+    /// Moves the current tetromino to the floor and locks it immediately,
+    /// spawning the next one. Returns the number of rows it fell, or `None`
+    /// if the tetromino could not be locked.
+    pub fn hard_drop(&mut self) -> Option<usize> {
+        if self.phase != Phase::Falling || self.game_over.is_some() {
+            return None;
+        }
+        let rows = self.sonic_drop();
+        self.scoring.hard_drop(rows);
+        if self.lock() {
+            Some(rows)
+        } else {
+            None
+        }
+    }
+
+    pub fn set_soft_drop(&mut self, active: bool) {
+        self.soft_drop = active;
+    }
+
+    pub fn set_soft_drop_factor(&mut self, factor: SoftDropFactor) {
+        self.soft_drop_factor = factor;
+    }
+
+    /// Sets how many 1/GRAVITY_UNIT rows the current tetromino falls per
+    /// tick.
+    pub fn set_gravity(&mut self, gravity: u32) {
+        self.gravity = gravity;
+    }
+
+    pub fn set_attack_table(&mut self, attack_table: AttackTable) {
+        self.attack_table = attack_table;
     }
 
-    fn tick(&mut self) -> bool {
-        if !self.move_down() {
-            if !self.board.can_put(&self.current_tetromino) {
-                return false;
+    pub fn set_level(&mut self, level: u32) {
+        if level > self.level {
+            self.emit(|| GameEvent::LevelUp { level });
+        }
+        self.level = level;
+    }
+
+    /// Advances the game by one tick. Returns false once the game is over.
+    pub fn tick(&mut self) -> bool {
+        if self.game_over.is_some() {
+            return false;
+        }
+        match &mut self.phase {
+            Phase::Falling => self.fall(),
+            Phase::LineClear { ticks_left, .. } => {
+                *ticks_left -= 1;
+                if *ticks_left == 0 {
+                    self.clear_lines();
+                    self.enter();
+                }
+                true
             }
-            self.board.put(&self.current_tetromino);
-            self.lines_removed += self.board.remove_full_lines();
-            self.spawn();
+            Phase::Entry { ticks_left } => {
+                *ticks_left -= 1;
+                if *ticks_left == 0 {
+                    self.spawn();
+                }
+                true
+            }
+        }
+    }
+
+    fn fall(&mut self) -> bool {
+        let rows = match (self.soft_drop, self.soft_drop_factor) {
+            (true, SoftDropFactor::Infinite) => self.board.height(),
+            (true, SoftDropFactor::Multiplier(multiplier)) => self.gravity_rows(multiplier),
+            (false, _) => self.gravity_rows(1),
+        };
+        if rows == 0 {
+            return true;
+        }
+        let mut moved = 0;
+        while moved < rows && self.move_down() {
+            moved += 1;
+        }
+        if moved == 0 {
+            return self.lock();
+        }
+        if self.soft_drop {
+            self.scoring.soft_drop(moved);
+        }
+        true
+    }
+
+    fn gravity_rows(&mut self, multiplier: usize) -> usize {
+        // In u64 so that a huge multiplier saturates instead of overflowing;
+        // more rows than the board has fall the same as the board height.
+        let progress = (self.gravity as u64)
+            .saturating_mul(multiplier as u64)
+            .saturating_add(self.gravity_progress as u64);
+        let unit = GRAVITY_UNIT as u64;
+        self.gravity_progress = (progress % unit) as u32;
+        (progress / unit).min(self.board.height() as u64) as usize
+    }
+
+    fn lock(&mut self) -> bool {
+        if !self.board.can_put(&self.current_tetromino) {
+            self.top_out(TopOut::LockOut);
+            return false;
+        }
+        let tspin = detect_tspin(&self.board, self.current_tetromino, self.last_rotation_kick);
+        self.board.put(&self.current_tetromino);
+        self.last_locked_tetromino = Some(self.current_tetromino);
+        let piece = self.current_tetromino;
+        self.emit(|| GameEvent::Locked { piece, tspin });
+        self.pieces_locked += 1;
+        self.invalidate_ghost();
+        let rows = self.board.full_lines();
+        let clear = Clear {
+            lines: rows.len(),
+            tspin,
+            perfect_clear: !rows.is_empty() && self.board.is_empty_after_clear(),
+        };
+        if !rows.is_empty() {
+            self.emit(|| GameEvent::LinesCleared {
+                rows: rows.clone(),
+                tspin,
+                perfect_clear: clear.perfect_clear,
+            });
+        }
+        let lock = self.scoring.lock(clear, self.level);
+        self.attack_sent += self.attack_table.attack(&lock);
+        self.last_lock = Some(lock);
+        if !rows.is_empty() && self.ruleset.line_clear_delay > 0 {
+            self.phase = Phase::LineClear {
+                rows,
+                ticks_left: self.ruleset.line_clear_delay,
+            };
+        } else {
+            self.clear_lines();
+            self.enter();
         }
         true
     }
 
-    fn rotate_clockwise(&mut self) -> bool {
-        self.update_tetromino(rotation_candidates(
-            self.current_tetromino,
-            Direction::Clockwise,
-        ))
+    fn clear_lines(&mut self) {
+        self.lines_removed += self.board.remove_full_lines();
+        self.invalidate_ghost();
+    }
+
+    fn enter(&mut self) {
+        if self.ruleset.entry_delay > 0 {
+            self.phase = Phase::Entry {
+                ticks_left: self.ruleset.entry_delay,
+            };
+        } else {
+            self.spawn();
+        }
     }
 
-    fn rotate_counterclockwise(&mut self) -> bool {
-        self.update_tetromino(rotation_candidates(
-            self.current_tetromino,
-            Direction::CounterClockwise,
-        ))
+    pub fn rotate_clockwise(&mut self) -> bool {
+        self.rotate(Direction::Clockwise)
+    }
+
+    pub fn rotate_counterclockwise(&mut self) -> bool {
+        self.rotate(Direction::CounterClockwise)
+    }
+
+    fn rotate(&mut self, direction: Direction) -> bool {
+        match self.try_candidates(rotation_candidates(self.current_tetromino, direction)) {
+            Some(kick) => {
+                self.last_rotation_kick = Some(kick);
+                let piece = self.current_tetromino;
+                self.emit(|| GameEvent::Rotated { piece, kick });
+                true
+            }
+            None => false,
+        }
     }
 
     fn update_tetromino(&mut self, candidates: Vec<Tetromino>) -> bool {
-        if let Some(t) = self.test_candidates(candidates) {
-            self.current_tetromino = t;
+        if self.try_candidates(candidates).is_some() {
+            self.last_rotation_kick = None;
+            self.emit_moved();
             return true;
         }
         false
     }
 
-    fn test_candidates(&self, candidates: Vec<Tetromino>) -> Option<Tetromino> {
-        for candidate in candidates {
-            if self.board.can_put(&candidate) {
-                return Some(candidate);
+    /// Moves the current tetromino to the first candidate that fits and
+    /// returns the candidate's index.
+    fn try_candidates(&mut self, candidates: Vec<Tetromino>) -> Option<usize> {
+        if self.phase != Phase::Falling || self.game_over.is_some() {
+            return None;
+        }
+        let (index, t) = self.test_candidates(candidates)?;
+        self.set_current_tetromino(t);
+        Some(index)
+    }
+
+    fn set_current_tetromino(&mut self, tetromino: Tetromino) {
+        self.current_tetromino = tetromino;
+        self.invalidate_ghost();
+    }
+
+    fn invalidate_ghost(&mut self) {
+        self.ghost.set(None);
+    }
+
+    fn test_candidates(&self, candidates: Vec<Tetromino>) -> Option<(usize, Tetromino)> {
+        candidates
+            .into_iter()
+            .enumerate()
+            .find(|(_, candidate)| self.board.can_put(candidate))
+    }
+
+    /// Pushes `count` garbage lines with their hole at `hole` in below the
+    /// stack. Returns false if that topped the player out.
+    pub fn push_garbage(&mut self, count: usize, hole: usize) -> bool {
+        let fits = self.board.push_garbage(count, hole);
+        self.garbage_pushed(count, fits)
+    }
+
+    /// Pushes arbitrary lines in below the stack, the first one ending up at
+    /// the bottom. Returns false if that topped the player out.
+    pub fn push_garbage_lines(&mut self, lines: &[Line]) -> bool {
+        let fits = self.board.push_lines(lines);
+        self.garbage_pushed(lines.len(), fits)
+    }
+
+    /// Tops out if blocks were pushed off the board, and otherwise moves a
+    /// falling tetromino that now overlaps the stack up until it fits.
+    fn garbage_pushed(&mut self, lines: usize, fits: bool) -> bool {
+        self.invalidate_ghost();
+        self.emit(|| GameEvent::GarbageReceived { lines });
+        if !fits {
+            self.top_out(TopOut::Garbage);
+        } else if self.phase == Phase::Falling {
+            let mut t = self.current_tetromino;
+            while !self.board.can_put(&t) && t.position.y < self.board.height() as i32 {
+                t = t.get_offset([0, 1]);
             }
+            if !self.board.can_put(&t) {
+                self.top_out(TopOut::Garbage);
+            } else if t != self.current_tetromino {
+                self.set_current_tetromino(t);
+                self.emit_moved();
+            }
+        }
+        self.game_over.is_none()
+    }
+
+    fn top_out(&mut self, reason: TopOut) {
+        if self.game_over.is_some() {
+            return;
+        }
+        self.game_over = Some(reason);
+        self.emit(|| GameEvent::GameOver);
+    }
+
+    /// Starts recording events, to be collected with `take_events`.
+    pub fn record_events(&mut self) {
+        self.events.get_or_insert_with(Vec::new);
+    }
+
+    /// Returns the events recorded since the last call.
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Registers a listener that is called with every event from now on.
+    pub fn add_listener<L: GameListener + 'static>(&mut self, listener: L) -> ListenerId {
+        let id = ListenerId(self.next_listener_id);
+        self.next_listener_id += 1;
+        self.listeners.push((id, Box::new(listener)));
+        id
+    }
+
+    pub fn remove_listener(&mut self, id: ListenerId) -> Option<Box<dyn GameListener>> {
+        let index = self.listeners.iter().position(|(other, _)| *other == id)?;
+        Some(self.listeners.remove(index).1)
+    }
+
+    /// Passes an event to the listeners and records it. The event is only
+    /// built if anyone is interested.
+    fn emit(&mut self, event: impl FnOnce() -> GameEvent) {
+        if self.events.is_none() && self.listeners.is_empty() {
+            return;
+        }
+        let event = event();
+        for (_, listener) in self.listeners.iter_mut() {
+            listener.on_event(&event);
+        }
+        if let Some(events) = &mut self.events {
+            events.push(event);
         }
-        None
     }
 
-    fn lines_removed(&self) -> usize {
+    fn emit_moved(&mut self) {
+        let piece = self.current_tetromino;
+        self.emit(|| GameEvent::Moved { piece });
+    }
+
+    pub fn lines_removed(&self) -> usize {
         self.lines_removed
     }
+
+    pub fn pieces_locked(&self) -> usize {
+        self.pieces_locked
+    }
+
+    /// Counts a key press by the player, for statistics like keys per piece.
+    pub fn register_key_press(&mut self) {
+        self.key_presses += 1;
+    }
+
+    pub fn key_presses(&self) -> usize {
+        self.key_presses
+    }
+
+    pub fn score(&self) -> u32 {
+        self.scoring.score()
+    }
+
+    pub fn scoring(&self) -> &Scoring {
+        &self.scoring
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// What the most recent lock cleared.
+    pub fn last_clear(&self) -> Option<Clear> {
+        self.last_lock.map(|lock| lock.clear)
+    }
+
+    pub fn last_lock(&self) -> Option<LockScore> {
+        self.last_lock
+    }
+
+    /// The tetromino that was locked last, where it was locked.
+    pub fn last_locked_tetromino(&self) -> Option<Tetromino> {
+        self.last_locked_tetromino
+    }
+
+    pub fn attack_table(&self) -> AttackTable {
+        self.attack_table
+    }
+
+    pub fn ruleset(&self) -> Ruleset {
+        self.ruleset
+    }
+
+    /// Total lines of garbage this game's clears were worth.
+    pub fn attack_sent(&self) -> u32 {
+        self.attack_sent
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.game_over.is_some()
+    }
+
+    /// Why the game ended, or `None` while it goes on.
+    pub fn top_out_reason(&self) -> Option<TopOut> {
+        self.game_over
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn phase(&self) -> &Phase {
+        &self.phase
+    }
+
+    /// Returns the falling tetromino. Outside of `Phase::Falling` this is the
+    /// tetromino that was locked last.
+    pub fn current_tetromino(&self) -> Tetromino {
+        self.current_tetromino
+    }
+
+    pub fn held_shape(&self) -> Option<Shape> {
+        self.held_shape
+    }
+
+    /// Whether hold can still be used for the current tetromino.
+    pub fn can_hold(&self) -> bool {
+        self.ruleset.hold && !self.hold_used && self.phase == Phase::Falling
+    }
+
+    /// The next `count` pieces after the current one, as far as the bag
+    /// already decided them.
+    pub fn preview(&self, count: usize) -> Vec<Shape> {
+        self.random_bag.preview(count)
+    }
+
+    /// Returns where the current tetromino would land if dropped. The result
+    /// is cached until the board or the current tetromino changes, so it is
+    /// cheap to call once per rendered frame.
+    pub fn ghost(&self) -> Tetromino {
+        if let Some(ghost) = self.ghost.get() {
+            return ghost;
+        }
+        let ghost = landing_position(&self.board, self.current_tetromino);
+        self.ghost.set(Some(ghost));
+        ghost
+    }
 }
 
-fn start_position() -> Position {
-    Position::new(4, 22)
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-fn spawn(shape: Shape) -> Tetromino {
-    Tetromino::new(start_position(), shape)
+pub(crate) fn spawn(shape: Shape) -> Tetromino {
+    Tetromino::spawn(shape)
 }
 
-fn rotation_candidates(t: Tetromino, direction: Direction) -> Vec<Tetromino> {
+/// Detects T-spins with the three corner rule. `kick` is the kick index of
+/// the rotation that moved the tetromino last, or `None` if it was not
+/// rotated last. A T-spin is a mini unless both corners the T points at are
+/// blocked or the last kick was used.
+pub fn detect_tspin(board: &Board, t: Tetromino, kick: Option<usize>) -> TSpin {
+    let kick = match kick {
+        Some(kick) if t.shape == Shape::T => kick,
+        _ => return TSpin::None,
+    };
+    let blocked = |corner: [i32; 2]| !board.is_free(t.position + corner);
+    let front = match t.orientation {
+        Orientation::North => [[-1, 1], [1, 1]],
+        Orientation::East => [[1, 1], [1, -1]],
+        Orientation::South => [[1, -1], [-1, -1]],
+        Orientation::West => [[-1, -1], [-1, 1]],
+    };
+    let back = front.map(|[x, y]| match t.orientation {
+        Orientation::North | Orientation::South => [x, -y],
+        Orientation::East | Orientation::West => [-x, y],
+    });
+    let front_blocked = front.iter().filter(|c| blocked(**c)).count();
+    let back_blocked = back.iter().filter(|c| blocked(**c)).count();
+    if front_blocked + back_blocked < 3 {
+        TSpin::None
+    } else if front_blocked == 2 || kick == 4 {
+        TSpin::Full
+    } else {
+        TSpin::Mini
+    }
+}
+
+pub(crate) fn landing_position(board: &Board, t: Tetromino) -> Tetromino {
+    let mut landed = t;
+    while board.can_put(&landed.get_moved_down()) {
+        landed = landed.get_moved_down();
+    }
+    landed
+}
+
+pub(crate) fn rotation_candidates(t: Tetromino, direction: Direction) -> Vec<Tetromino> {
     let new_t = match direction {
         Direction::Clockwise => t.get_rotated_clockwise(),
         Direction::CounterClockwise => t.get_rotated_counterclockwise(),
@@ -174,6 +751,9 @@ fn offset_table_o(orientation: Orientation) -> Vec<[i32; 2]> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::position::Position;
+    use crate::tetromino::SPAWN_POSITION;
+    use std::sync::{mpsc, Arc, Mutex};
 
     fn drop_left(game: &mut Game) -> usize {
         let mut move_cnt: usize = 0;
@@ -194,7 +774,7 @@ mod tests {
     #[test]
     fn test_starting_position() {
         let mut game = Game::new();
-        assert_eq!(game.current_tetromino.position, start_position());
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION);
         for orientation in [
             Orientation::North,
             Orientation::East,
@@ -207,11 +787,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_first_piece_comes_from_the_bag() {
+        for seed in 0..8 {
+            let game = Game::with_seed(Ruleset::default(), seed);
+            let mut bag = RandomBag::with_seed(seed);
+            assert_eq!(game.current_tetromino, spawn(bag.get()));
+            assert_eq!(
+                game.preview(5),
+                (0..5).map(|_| bag.get()).collect::<Vec<_>>()
+            );
+        }
+    }
+
     #[test]
     fn test_spawn() {
         let mut game = Game::new();
         game.spawn();
-        assert!(game.current_tetromino.position == start_position());
+        assert!(game.current_tetromino.position == SPAWN_POSITION);
         assert!(game.current_tetromino.orientation == Orientation::North);
     }
 
@@ -251,7 +844,7 @@ mod tests {
         game.spawn_specific(Shape::I);
         let expected_orientation = game.current_tetromino.get_rotated_clockwise().orientation;
         assert!(game.rotate_clockwise());
-        assert_eq!(game.current_tetromino.position, start_position() + [1, 0]);
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION + [1, 0]);
         assert_eq!(game.current_tetromino.orientation, expected_orientation);
     }
 
@@ -261,9 +854,9 @@ mod tests {
         game.spawn_specific(Shape::I);
         game.rotate_clockwise();
         drop_left(&mut game);
-        assert_eq!(game.current_tetromino.position, start_position() - [4, 0]);
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION - [4, 0]);
         game.rotate_counterclockwise();
-        assert_eq!(game.current_tetromino.position, start_position() - [3, 0]);
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION - [3, 0]);
         assert_eq!(game.current_tetromino.orientation, Orientation::North);
     }
 
@@ -272,11 +865,11 @@ mod tests {
         let mut game = Game::new();
         game.spawn_specific(Shape::O);
         drop_left(&mut game);
-        assert_eq!(game.current_tetromino.position, start_position() - [4, 0]);
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION - [4, 0]);
         for _ in 0..4 {
             game.rotate_clockwise();
         }
-        assert_eq!(game.current_tetromino.position, start_position() - [4, 0]);
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION - [4, 0]);
     }
 
     #[test]
@@ -288,16 +881,96 @@ mod tests {
             .get_rotated_counterclockwise()
             .orientation;
         assert!(game.rotate_counterclockwise());
-        assert_eq!(game.current_tetromino.position, start_position() + [0, -1]);
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION + [0, -1]);
         assert_eq!(game.current_tetromino.orientation, expected_orientation);
     }
 
     #[test]
-    fn test_drop() {
+    fn test_sonic_drop() {
         let mut game = Game::new();
         game.spawn_specific(Shape::I);
-        game.drop();
+        assert_eq!(game.sonic_drop(), 22);
         assert_eq!(game.current_tetromino.position, Position::new(4, 0));
+        assert_eq!(game.sonic_drop(), 0);
+    }
+
+    #[test]
+    fn test_hard_drop() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        game.move_down();
+        assert_eq!(game.hard_drop(), Some(21));
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION);
+        assert!(!game
+            .board
+            .can_put(&Tetromino::new(Position::new(4, 0), Shape::I)));
+    }
+
+    #[test]
+    fn test_hard_drop_clears_line() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        drop_left(&mut game);
+        game.hard_drop();
+        game.spawn_specific(Shape::I);
+        drop_right(&mut game);
+        game.hard_drop();
+        assert_eq!(game.lines_removed(), 0);
+        game.spawn_specific(Shape::O);
+        assert_eq!(game.hard_drop(), Some(22));
+        assert_eq!(game.lines_removed(), 1);
+        assert!(!game
+            .board
+            .can_put(&Tetromino::new(Position::new(4, 0), Shape::O)));
+        assert!(game
+            .board
+            .can_put(&Tetromino::new(Position::new(4, 1), Shape::O)));
+    }
+
+    #[test]
+    fn test_soft_drop() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        game.set_soft_drop_factor(SoftDropFactor::Multiplier(5));
+        game.set_soft_drop(true);
+        assert!(game.tick());
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION - [0, 5]);
+        game.set_soft_drop(false);
+        assert!(game.tick());
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION - [0, 6]);
+        game.set_soft_drop(true);
+        for _ in 0..4 {
+            assert!(game.tick());
+        }
+        assert_eq!(game.current_tetromino.position, Position::new(4, 0));
+        assert!(game.tick());
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION);
+    }
+
+    #[test]
+    fn test_huge_soft_drop_multiplier() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        game.set_gravity(u32::MAX);
+        game.set_soft_drop_factor(SoftDropFactor::Multiplier(usize::MAX));
+        game.set_soft_drop(true);
+        assert!(game.tick());
+        assert_eq!(game.current_tetromino.position, Position::new(4, 0));
+        assert!(game.tick());
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION);
+    }
+
+    #[test]
+    fn test_infinite_soft_drop_does_not_lock() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        game.set_soft_drop_factor(SoftDropFactor::Infinite);
+        game.set_soft_drop(true);
+        assert!(game.tick());
+        assert_eq!(game.current_tetromino.position, Position::new(4, 0));
+        assert!(game.move_left());
+        assert!(game.tick());
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION);
     }
 
     #[test]
@@ -305,11 +978,11 @@ mod tests {
         let mut game = Game::new();
         game.spawn();
         assert!(game.tick());
-        assert_eq!(game.current_tetromino.position, start_position() - [0, 1]);
-        game.drop();
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION - [0, 1]);
+        game.sonic_drop();
         assert!(game.tick());
         game.spawn_specific(Shape::I);
-        assert_eq!(game.current_tetromino.position, start_position());
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION);
         game.move_down();
         game.put_current_tetromino();
         game.spawn();
@@ -322,20 +995,434 @@ mod tests {
         let mut game = Game::new();
         game.spawn_specific(Shape::I);
         drop_left(&mut game);
-        game.drop();
+        game.sonic_drop();
         game.tick();
         game.spawn_specific(Shape::I);
         drop_right(&mut game);
-        game.drop();
+        game.sonic_drop();
         game.tick();
         game.spawn_specific(Shape::I);
         game.rotate_clockwise();
-        game.drop();
+        game.sonic_drop();
         game.tick();
         game.spawn_specific(Shape::I);
         game.rotate_counterclockwise();
-        game.drop();
+        game.sonic_drop();
         game.tick();
         assert_eq!(game.lines_removed(), 1);
     }
+
+    #[test]
+    fn test_hold() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::T);
+        let next = game.random_bag.peek();
+        assert!(game.hold());
+        assert_eq!(game.held_shape(), Some(Shape::T));
+        assert_eq!(game.current_tetromino.shape, next);
+        assert!(!game.hold());
+        game.hard_drop();
+        game.move_left();
+        let current = game.current_tetromino.shape;
+        assert!(game.hold());
+        assert_eq!(game.held_shape(), Some(current));
+        assert_eq!(game.current_tetromino.shape, Shape::T);
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION);
+    }
+
+    #[test]
+    fn test_initial_rotation() {
+        let mut game = Game::new();
+        game.buffer_rotation(Some(Direction::Clockwise));
+        game.hard_drop();
+        assert_eq!(game.current_tetromino.orientation, Orientation::East);
+        game.buffer_rotation(None);
+        game.hard_drop();
+        assert_eq!(game.current_tetromino.orientation, Orientation::North);
+    }
+
+    #[test]
+    fn test_initial_hold() {
+        let mut game = Game::new();
+        game.buffer_hold(true);
+        let next = game.random_bag.peek();
+        game.hard_drop();
+        assert_eq!(game.held_shape(), Some(next));
+        assert!(!game.hold());
+        game.hard_drop();
+        assert_eq!(game.held_shape(), Some(next));
+        assert!(game.hold());
+    }
+
+    #[test]
+    fn test_hold_disabled() {
+        let mut game = Game::with_ruleset(Ruleset::nes());
+        let current = game.current_tetromino.shape;
+        assert!(!game.can_hold());
+        assert!(!game.hold());
+        assert_eq!(game.held_shape(), None);
+        assert_eq!(game.current_tetromino.shape, current);
+    }
+
+    #[test]
+    fn test_initial_systems_disabled() {
+        let mut game = Game::with_ruleset(Ruleset {
+            initial_rotation: false,
+            initial_hold: false,
+            ..Ruleset::default()
+        });
+        game.buffer_rotation(Some(Direction::CounterClockwise));
+        game.buffer_hold(true);
+        game.hard_drop();
+        assert_eq!(game.current_tetromino.orientation, Orientation::North);
+        assert_eq!(game.held_shape(), None);
+        assert!(game.hold());
+    }
+
+    fn fill_line_but_middle(game: &mut Game) {
+        game.spawn_specific(Shape::I);
+        drop_left(game);
+        game.hard_drop();
+        game.spawn_specific(Shape::I);
+        drop_right(game);
+        game.hard_drop();
+        game.spawn_specific(Shape::O);
+    }
+
+    #[test]
+    fn test_entry_delay() {
+        let mut game = Game::with_ruleset(Ruleset {
+            entry_delay: 2,
+            ..Ruleset::default()
+        });
+        game.hard_drop();
+        assert_eq!(game.phase(), &Phase::Entry { ticks_left: 2 });
+        assert!(!game.move_left());
+        assert!(!game.hold());
+        assert!(game.tick());
+        assert_eq!(game.phase(), &Phase::Entry { ticks_left: 1 });
+        assert!(game.tick());
+        assert_eq!(game.phase(), &Phase::Falling);
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION);
+    }
+
+    #[test]
+    fn test_line_clear_delay() {
+        let mut game = Game::with_ruleset(Ruleset {
+            entry_delay: 1,
+            line_clear_delay: 2,
+            ..Ruleset::default()
+        });
+        fill_line_but_middle(&mut game);
+        game.hard_drop();
+        assert_eq!(
+            game.phase(),
+            &Phase::LineClear {
+                rows: vec![0],
+                ticks_left: 2
+            }
+        );
+        assert_eq!(game.lines_removed(), 0);
+        game.tick();
+        game.tick();
+        assert_eq!(game.lines_removed(), 1);
+        assert_eq!(game.phase(), &Phase::Entry { ticks_left: 1 });
+        game.tick();
+        assert_eq!(game.phase(), &Phase::Falling);
+    }
+
+    #[test]
+    fn test_gravity() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        game.set_gravity(GRAVITY_UNIT / 4);
+        for _ in 0..3 {
+            game.tick();
+        }
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION);
+        game.tick();
+        assert_eq!(game.current_tetromino.position, SPAWN_POSITION - [0, 1]);
+        game.set_gravity(GRAVITY_UNIT * 20);
+        game.tick();
+        assert_eq!(game.current_tetromino.position, Position::new(4, 1));
+        game.set_soft_drop_factor(SoftDropFactor::Multiplier(2));
+        game.set_soft_drop(true);
+        game.set_gravity(GRAVITY_UNIT / 2);
+        game.tick();
+        assert_eq!(game.current_tetromino.position, Position::new(4, 0));
+        assert_eq!(game.score(), 1);
+    }
+
+    #[test]
+    fn test_game_over_on_spawn() {
+        let mut game = Game::new();
+        game.record_events();
+        for _ in 0..20 {
+            game.spawn_specific(Shape::O);
+            game.hard_drop();
+        }
+        assert!(game.is_game_over());
+        assert_eq!(game.top_out_reason(), Some(TopOut::BlockOut));
+        assert!(!game.tick());
+        assert_eq!(game.hard_drop(), None);
+        let game_overs = game
+            .take_events()
+            .into_iter()
+            .filter(|event| *event == GameEvent::GameOver)
+            .count();
+        assert_eq!(game_overs, 1);
+    }
+
+    #[test]
+    fn test_scoring() {
+        let mut game = Game::new();
+        fill_line_but_middle(&mut game);
+        game.set_level(2);
+        let score = game.score();
+        assert_eq!(game.hard_drop(), Some(22));
+        assert_eq!(game.score(), score + 44 + 200);
+        assert_eq!(
+            game.last_clear(),
+            Some(Clear {
+                lines: 1,
+                tspin: TSpin::None,
+                perfect_clear: false
+            })
+        );
+    }
+
+    fn put_blocks(game: &mut Game, blocks: &[[i32; 2]]) {
+        for block in blocks {
+            game.board.set(Position::new(block[0], block[1]), Shape::I);
+        }
+    }
+
+    #[test]
+    fn test_tspin_double() {
+        let mut game = Game::new();
+        let mut blocks = vec![];
+        for x in 0..10 {
+            if x != 4 {
+                blocks.push([x, 0]);
+            }
+            if !(3..=5).contains(&x) {
+                blocks.push([x, 1]);
+            }
+        }
+        blocks.push([3, 2]);
+        put_blocks(&mut game, &blocks);
+        game.spawn_specific(Shape::T);
+        game.rotate_clockwise();
+        game.sonic_drop();
+        assert_eq!(game.current_tetromino.position, Position::new(4, 1));
+        game.rotate_clockwise();
+        assert_eq!(game.current_tetromino.orientation, Orientation::South);
+        assert_eq!(game.current_tetromino.position, Position::new(4, 1));
+        assert!(game.hard_drop().is_some());
+        assert_eq!(
+            game.last_clear(),
+            Some(Clear {
+                lines: 2,
+                tspin: TSpin::Full,
+                perfect_clear: false
+            })
+        );
+        assert_eq!(game.lines_removed(), 2);
+        assert_eq!(game.attack_sent(), 4);
+    }
+
+    #[test]
+    fn test_rotation_without_corners_is_no_tspin() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::T);
+        game.sonic_drop();
+        game.rotate_clockwise();
+        game.rotate_counterclockwise();
+        game.hard_drop();
+        assert_eq!(
+            game.last_clear(),
+            Some(Clear {
+                lines: 0,
+                tspin: TSpin::None,
+                perfect_clear: false
+            })
+        );
+    }
+
+    #[test]
+    fn test_push_garbage_moves_tetromino_up() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        game.sonic_drop();
+        assert!(game.push_garbage(3, 0));
+        assert_eq!(game.current_tetromino.position, Position::new(4, 3));
+        assert_eq!(game.ghost().position, Position::new(4, 3));
+        game.move_left();
+        game.move_left();
+        game.move_left();
+        assert_eq!(game.ghost().position, Position::new(1, 3));
+    }
+
+    #[test]
+    fn test_no_events_after_block_out() {
+        let mut game = Game::new();
+        // Blocks only the right end of the spawn, so the T could move left.
+        let cells = spawn(Shape::T).cells();
+        let right = *cells.iter().max_by_key(|cell| cell.x).unwrap();
+        game.board.set(right, Shape::I);
+        game.spawn_specific(Shape::T);
+        assert_eq!(game.top_out_reason(), Some(TopOut::BlockOut));
+        game.record_events();
+        assert!(!game.move_left());
+        assert!(!game.move_right());
+        assert!(!game.move_down());
+        assert!(!game.rotate_clockwise());
+        assert!(!game.rotate_counterclockwise());
+        assert!(!game.hold());
+        assert_eq!(game.hard_drop(), None);
+        assert!(!game.tick());
+        assert!(game.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_lock_out() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::O);
+        let cell = game.current_tetromino.cells()[0];
+        game.board.set(cell, Shape::I);
+        assert_eq!(game.hard_drop(), None);
+        assert_eq!(game.top_out_reason(), Some(TopOut::LockOut));
+    }
+
+    #[test]
+    fn test_push_garbage_tops_out() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        assert!(game.push_garbage(20, 0));
+        assert!(!game.is_game_over());
+        assert!(!game.push_garbage(4, 0));
+        assert_eq!(game.top_out_reason(), Some(TopOut::Garbage));
+        assert!(!game.tick());
+    }
+
+    #[test]
+    fn test_listeners() {
+        let mut game = Game::new();
+        let (sender, receiver) = mpsc::channel();
+        game.add_listener(sender);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_by_closure = Arc::clone(&seen);
+        let id = game.add_listener(move |event: &GameEvent| {
+            seen_by_closure.lock().unwrap().push(event.clone());
+        });
+
+        game.spawn_specific(Shape::T);
+        game.hold();
+        game.set_level(2);
+        game.set_level(2);
+        let events: Vec<GameEvent> = receiver.try_iter().collect();
+        assert_eq!(
+            events[0],
+            GameEvent::Spawned {
+                piece: spawn(Shape::T)
+            }
+        );
+        assert_eq!(events[1], GameEvent::Held { shape: Shape::T });
+        assert!(matches!(events[2], GameEvent::Spawned { .. }));
+        assert_eq!(events[3], GameEvent::LevelUp { level: 2 });
+        assert_eq!(events.len(), 4);
+        assert_eq!(*seen.lock().unwrap(), events);
+
+        assert!(game.remove_listener(id).is_some());
+        assert!(game.remove_listener(id).is_none());
+        game.move_left();
+        assert_eq!(receiver.try_iter().count(), 1);
+        assert_eq!(seen.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_events() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::O);
+        game.move_left();
+        assert!(game.take_events().is_empty());
+
+        game.record_events();
+        game.spawn_specific(Shape::I);
+        let spawned = game.current_tetromino;
+        game.rotate_clockwise();
+        let rotated = game.current_tetromino;
+        game.move_left();
+        let moved = game.current_tetromino;
+        game.put_current_tetromino();
+        let events = game.take_events();
+        assert_eq!(
+            events[..4],
+            [
+                GameEvent::Spawned { piece: spawned },
+                GameEvent::Rotated {
+                    piece: rotated,
+                    kick: 0
+                },
+                GameEvent::Moved { piece: moved },
+                GameEvent::Locked {
+                    piece: moved,
+                    tspin: TSpin::None
+                },
+            ]
+        );
+        assert!(matches!(events[4], GameEvent::Spawned { .. }));
+        assert!(game.take_events().is_empty());
+
+        let mut game = Game::new();
+        fill_line_but_middle(&mut game);
+        game.record_events();
+        game.hard_drop();
+        let events = game.take_events();
+        assert!(events.contains(&GameEvent::LinesCleared {
+            rows: vec![0],
+            tspin: TSpin::None,
+            perfect_clear: false
+        }));
+
+        game.push_garbage(30, 0);
+        assert_eq!(
+            game.take_events(),
+            [
+                GameEvent::GarbageReceived { lines: 30 },
+                GameEvent::GameOver
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ghost() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        assert_eq!(game.ghost().position, Position::new(4, 0));
+        assert_eq!(game.ghost().orientation, Orientation::North);
+        game.move_down();
+        assert_eq!(game.ghost().position, Position::new(4, 0));
+        game.rotate_clockwise();
+        assert_eq!(game.ghost().position, Position::new(5, 2));
+        assert_eq!(game.ghost().orientation, Orientation::East);
+        game.sonic_drop();
+        assert_eq!(game.ghost().position, game.current_tetromino.position);
+    }
+
+    #[test]
+    fn test_ghost_follows_stack() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        game.sonic_drop();
+        game.tick();
+        game.spawn_specific(Shape::I);
+        assert_eq!(game.ghost().position, Position::new(4, 1));
+        game.move_left();
+        assert_eq!(game.ghost().position, Position::new(3, 1));
+        drop_left(&mut game);
+        assert_eq!(game.ghost().position, Position::new(1, 1));
+        drop_right(&mut game);
+        assert_eq!(game.ghost().position, Position::new(7, 1));
+    }
 }
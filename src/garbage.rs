@@ -0,0 +1,93 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Chooses the hole column of successive garbage lines. With `messiness`
+/// zero every line has its hole in the same column, with one the hole moves
+/// on every line. Messiness outside of that range is clamped.
+pub struct GarbageGenerator {
+    messiness: f64,
+    width: usize,
+    hole: usize,
+    rng: StdRng,
+}
+
+impl GarbageGenerator {
+    /// Panics if `width` is zero.
+    pub fn new(messiness: f64, width: usize) -> Self {
+        Self::with_rng(messiness, width, StdRng::from_entropy())
+    }
+
+    pub fn with_seed(messiness: f64, width: usize, seed: u64) -> Self {
+        Self::with_rng(messiness, width, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(messiness: f64, width: usize, mut rng: StdRng) -> Self {
+        assert!(width > 0, "garbage needs a board at least one column wide");
+        let hole = rng.gen_range(0..width);
+        GarbageGenerator {
+            // NaN is not clamped, so treat it as clean garbage.
+            messiness: if messiness.is_nan() {
+                0.0
+            } else {
+                messiness.clamp(0.0, 1.0)
+            },
+            width,
+            hole,
+            rng,
+        }
+    }
+
+    pub fn next_hole(&mut self) -> usize {
+        let hole = self.hole;
+        // A single column leaves nowhere else for the hole to go.
+        if self.width > 1 && self.rng.gen_bool(self.messiness) {
+            let offset = self.rng.gen_range(1..self.width);
+            self.hole = (self.hole + offset) % self.width;
+        }
+        hole
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holes(generator: &mut GarbageGenerator, count: usize) -> Vec<usize> {
+        (0..count).map(|_| generator.next_hole()).collect()
+    }
+
+    #[test]
+    fn test_clean_garbage() {
+        let mut generator = GarbageGenerator::new(0.0, 10);
+        let holes = holes(&mut generator, 20);
+        assert!(holes.iter().all(|hole| *hole == holes[0] && *hole < 10));
+    }
+
+    #[test]
+    fn test_messy_garbage() {
+        let mut generator = GarbageGenerator::new(1.0, 10);
+        let holes = holes(&mut generator, 20);
+        assert!(holes.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn test_out_of_range_settings() {
+        let mut generator = GarbageGenerator::new(2.5, 10);
+        let messy = holes(&mut generator, 20);
+        assert!(messy.windows(2).all(|pair| pair[0] != pair[1]));
+        let mut generator = GarbageGenerator::new(-1.0, 10);
+        let clean = holes(&mut generator, 20);
+        assert!(clean.iter().all(|hole| *hole == clean[0]));
+        let mut generator = GarbageGenerator::new(f64::NAN, 1);
+        assert_eq!(holes(&mut generator, 5), vec![0; 5]);
+        let mut generator = GarbageGenerator::new(1.0, 1);
+        assert_eq!(holes(&mut generator, 5), vec![0; 5]);
+    }
+
+    #[test]
+    fn test_seeded_garbage() {
+        let mut a = GarbageGenerator::with_seed(0.5, 10, 7);
+        let mut b = GarbageGenerator::with_seed(0.5, 10, 7);
+        assert_eq!(holes(&mut a, 50), holes(&mut b, 50));
+    }
+}
@@ -0,0 +1,480 @@
+use crate::game::{Direction, Game, Phase, SoftDropFactor};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "python", pyo3::pyclass(eq, eq_int))]
+pub enum Key {
+    Left,
+    Right,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    Hold,
+}
+
+/// What happens while left and right are held at the same time.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DirectionPriority {
+    /// The most recently pressed direction is used.
+    LastPressed,
+    /// Neither direction moves the tetromino.
+    Cancel,
+}
+
+/// Player handling settings. All delays are measured in frames.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Handling {
+    /// Delayed Auto Shift: how long a direction has to be held before it
+    /// starts repeating.
+    pub das: u32,
+    /// Auto Repeat Rate: frames between repeated moves once DAS is charged.
+    /// Zero moves the tetromino all the way to the wall instantly.
+    pub arr: u32,
+    pub soft_drop_factor: SoftDropFactor,
+    /// DAS Cut Delay: how long auto shift pauses after a rotation or a hard
+    /// drop while DAS is charged.
+    pub das_cut_delay: u32,
+    pub priority: DirectionPriority,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Handling {
+            das: 10,
+            arr: 2,
+            soft_drop_factor: SoftDropFactor::Multiplier(20),
+            das_cut_delay: 1,
+            priority: DirectionPriority::LastPressed,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Shift {
+    Left,
+    Right,
+}
+
+/// Converts raw key events into `Game` actions. Events are queued and only
+/// applied by `update`, which has to be called exactly once per frame, so the
+/// result depends only on the frame each event arrived in.
+pub struct InputHandler {
+    handling: Handling,
+    events: Vec<(Key, bool)>,
+    left_held: bool,
+    right_held: bool,
+    /// The rotation keys held down, the most recently pressed last.
+    rotations_held: Vec<Direction>,
+    last_shift: Shift,
+    das_timer: u32,
+    arr_timer: u32,
+    das_cut_timer: u32,
+}
+
+impl InputHandler {
+    pub fn new(handling: Handling) -> Self {
+        InputHandler {
+            handling,
+            events: Vec::new(),
+            left_held: false,
+            right_held: false,
+            rotations_held: Vec::new(),
+            last_shift: Shift::Left,
+            das_timer: 0,
+            arr_timer: 0,
+            das_cut_timer: 0,
+        }
+    }
+
+    pub fn handling(&self) -> Handling {
+        self.handling
+    }
+
+    pub fn key_down(&mut self, key: Key) {
+        self.events.push((key, true));
+    }
+
+    pub fn key_up(&mut self, key: Key) {
+        self.events.push((key, false));
+    }
+
+    /// Applies the queued key events and auto shift for one frame.
+    pub fn update(&mut self, game: &mut Game) {
+        let events: Vec<(Key, bool)> = self.events.drain(..).collect();
+        let mut shifted = false;
+        for (key, down) in events {
+            if down {
+                game.register_key_press();
+            }
+            match (key, down) {
+                (Key::Left, true) => {
+                    self.left_held = true;
+                    shifted |= self.press_shift(game, Shift::Left);
+                }
+                (Key::Right, true) => {
+                    self.right_held = true;
+                    shifted |= self.press_shift(game, Shift::Right);
+                }
+                (Key::Left, false) => {
+                    self.left_held = false;
+                    self.release_shift();
+                }
+                (Key::Right, false) => {
+                    self.right_held = false;
+                    self.release_shift();
+                }
+                (Key::SoftDrop, down) => {
+                    game.set_soft_drop_factor(self.handling.soft_drop_factor);
+                    game.set_soft_drop(down);
+                }
+                (Key::HardDrop, true) => {
+                    game.hard_drop();
+                    self.cut_das();
+                }
+                (Key::RotateClockwise, true) => {
+                    self.press_rotation(game, Direction::Clockwise);
+                }
+                (Key::RotateCounterClockwise, true) => {
+                    self.press_rotation(game, Direction::CounterClockwise);
+                }
+                (Key::RotateClockwise, false) => {
+                    self.release_rotation(game, Direction::Clockwise);
+                }
+                (Key::RotateCounterClockwise, false) => {
+                    self.release_rotation(game, Direction::CounterClockwise);
+                }
+                (Key::Hold, true) => {
+                    // Only a press while no piece is falling is kept for
+                    // the next spawn, or a piece spawning this frame would
+                    // be held as well.
+                    if *game.phase() == Phase::Falling {
+                        game.hold();
+                    } else {
+                        game.buffer_hold(true);
+                    }
+                }
+                (Key::Hold, false) => game.buffer_hold(false),
+                (Key::HardDrop, false) => (),
+            }
+        }
+        if !shifted {
+            self.auto_shift(game);
+        }
+    }
+
+    fn active_shift(&self) -> Option<Shift> {
+        match (self.left_held, self.right_held) {
+            (true, false) => Some(Shift::Left),
+            (false, true) => Some(Shift::Right),
+            (true, true) => match self.handling.priority {
+                DirectionPriority::LastPressed => Some(self.last_shift),
+                DirectionPriority::Cancel => None,
+            },
+            (false, false) => None,
+        }
+    }
+
+    fn press_shift(&mut self, game: &mut Game, shift: Shift) -> bool {
+        self.last_shift = shift;
+        self.reset_das();
+        match self.active_shift() {
+            Some(active) => {
+                shift_once(game, active);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn press_rotation(&mut self, game: &mut Game, direction: Direction) {
+        let rotated = match direction {
+            Direction::Clockwise => game.rotate_clockwise(),
+            Direction::CounterClockwise => game.rotate_counterclockwise(),
+        };
+        if rotated {
+            self.cut_das();
+        }
+        self.rotations_held.retain(|held| *held != direction);
+        self.rotations_held.push(direction);
+        game.buffer_rotation(Some(direction));
+    }
+
+    fn release_rotation(&mut self, game: &mut Game, direction: Direction) {
+        self.rotations_held.retain(|held| *held != direction);
+        game.buffer_rotation(self.rotations_held.last().copied());
+    }
+
+    fn release_shift(&mut self) {
+        self.reset_das();
+    }
+
+    fn reset_das(&mut self) {
+        self.das_timer = 0;
+        self.arr_timer = 0;
+        self.das_cut_timer = 0;
+    }
+
+    fn cut_das(&mut self) {
+        if self.active_shift().is_some() && self.das_timer >= self.handling.das {
+            self.das_cut_timer = self.handling.das_cut_delay;
+        }
+    }
+
+    fn auto_shift(&mut self, game: &mut Game) {
+        let shift = match self.active_shift() {
+            Some(shift) => shift,
+            None => return,
+        };
+        if self.das_timer < self.handling.das {
+            self.das_timer += 1;
+            if self.das_timer < self.handling.das {
+                return;
+            }
+        } else if self.das_cut_timer > 0 {
+            self.das_cut_timer -= 1;
+            return;
+        } else {
+            self.arr_timer += 1;
+            if self.arr_timer < self.handling.arr {
+                return;
+            }
+        }
+        self.arr_timer = 0;
+        if self.handling.arr == 0 {
+            while shift_once(game, shift) {}
+        } else {
+            shift_once(game, shift);
+        }
+    }
+}
+
+/// Applies a single key press directly, without handling or repeats, and
+/// counts it. Returns whether the game accepted it.
+pub fn press(game: &mut Game, key: Key) -> bool {
+    game.register_key_press();
+    match key {
+        Key::Left => game.move_left(),
+        Key::Right => game.move_right(),
+        Key::SoftDrop => game.move_down(),
+        Key::HardDrop => game.hard_drop().is_some(),
+        Key::RotateClockwise => game.rotate_clockwise(),
+        Key::RotateCounterClockwise => game.rotate_counterclockwise(),
+        Key::Hold => game.hold(),
+    }
+}
+
+fn shift_once(game: &mut Game, shift: Shift) -> bool {
+    match shift {
+        Shift::Left => game.move_left(),
+        Shift::Right => game.move_right(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ruleset::Ruleset;
+    use crate::tetromino::{Orientation, Shape};
+
+    fn handling(das: u32, arr: u32) -> Handling {
+        Handling {
+            das,
+            arr,
+            ..Handling::default()
+        }
+    }
+
+    fn x(game: &Game) -> i32 {
+        game.current_tetromino().position.x
+    }
+
+    fn run_frames(input: &mut InputHandler, game: &mut Game, frames: usize) {
+        for _ in 0..frames {
+            input.update(game);
+        }
+    }
+
+    #[test]
+    fn test_tap() {
+        let mut game = Game::new();
+        let mut input = InputHandler::new(handling(10, 2));
+        input.key_down(Key::Left);
+        input.key_up(Key::Left);
+        input.update(&mut game);
+        assert_eq!(x(&game), 3);
+        run_frames(&mut input, &mut game, 20);
+        assert_eq!(x(&game), 3);
+    }
+
+    #[test]
+    fn test_key_presses_are_counted() {
+        let mut game = Game::new();
+        let mut input = InputHandler::new(handling(1, 1));
+        input.key_down(Key::Left);
+        run_frames(&mut input, &mut game, 5);
+        input.key_up(Key::Left);
+        input.key_down(Key::HardDrop);
+        input.update(&mut game);
+        assert_eq!(game.key_presses(), 2);
+    }
+
+    #[test]
+    fn test_das_and_arr() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        let mut input = InputHandler::new(handling(3, 2));
+        input.key_down(Key::Right);
+        input.update(&mut game);
+        assert_eq!(x(&game), 5);
+        run_frames(&mut input, &mut game, 2);
+        assert_eq!(x(&game), 5);
+        input.update(&mut game);
+        assert_eq!(x(&game), 6);
+        input.update(&mut game);
+        assert_eq!(x(&game), 6);
+        input.update(&mut game);
+        assert_eq!(x(&game), 7);
+        run_frames(&mut input, &mut game, 10);
+        assert_eq!(x(&game), 7);
+    }
+
+    #[test]
+    fn test_instant_arr() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        let mut input = InputHandler::new(handling(3, 0));
+        input.key_down(Key::Left);
+        run_frames(&mut input, &mut game, 3);
+        assert_eq!(x(&game), 3);
+        input.update(&mut game);
+        assert_eq!(x(&game), 1);
+    }
+
+    #[test]
+    fn test_last_pressed_priority() {
+        let mut game = Game::new();
+        let mut input = InputHandler::new(handling(10, 1));
+        input.key_down(Key::Left);
+        input.update(&mut game);
+        input.key_down(Key::Right);
+        input.update(&mut game);
+        assert_eq!(x(&game), 4);
+        input.key_up(Key::Right);
+        run_frames(&mut input, &mut game, 9);
+        assert_eq!(x(&game), 4);
+        input.update(&mut game);
+        assert_eq!(x(&game), 3);
+    }
+
+    #[test]
+    fn test_cancel_priority() {
+        let mut game = Game::new();
+        let mut input = InputHandler::new(Handling {
+            priority: DirectionPriority::Cancel,
+            ..handling(2, 1)
+        });
+        input.key_down(Key::Left);
+        input.update(&mut game);
+        input.key_down(Key::Right);
+        run_frames(&mut input, &mut game, 10);
+        assert_eq!(x(&game), 3);
+    }
+
+    #[test]
+    fn test_das_cut_delay() {
+        let mut game = Game::new();
+        let mut input = InputHandler::new(Handling {
+            das_cut_delay: 2,
+            ..handling(2, 1)
+        });
+        input.key_down(Key::Left);
+        run_frames(&mut input, &mut game, 3);
+        assert_eq!(x(&game), 2);
+        input.key_down(Key::RotateClockwise);
+        input.key_up(Key::RotateClockwise);
+        input.update(&mut game);
+        let rotated_x = x(&game);
+        input.update(&mut game);
+        assert_eq!(x(&game), rotated_x);
+        input.update(&mut game);
+        assert_eq!(x(&game), rotated_x - 1);
+    }
+
+    #[test]
+    fn test_soft_drop_and_hard_drop() {
+        let mut game = Game::new();
+        let mut input = InputHandler::new(Handling {
+            soft_drop_factor: SoftDropFactor::Infinite,
+            ..Handling::default()
+        });
+        input.key_down(Key::SoftDrop);
+        input.update(&mut game);
+        game.tick();
+        assert_eq!(game.current_tetromino().position.y, 0);
+        input.key_up(Key::SoftDrop);
+        input.key_down(Key::HardDrop);
+        input.update(&mut game);
+        assert_eq!(game.current_tetromino().position.y, 22);
+    }
+
+    #[test]
+    fn test_held_keys_are_buffered_for_spawn() {
+        let mut game = Game::new();
+        let mut input = InputHandler::new(Handling::default());
+        input.key_down(Key::RotateCounterClockwise);
+        input.key_down(Key::HardDrop);
+        input.update(&mut game);
+        assert_eq!(game.current_tetromino().orientation, Orientation::West);
+        input.key_up(Key::RotateCounterClockwise);
+        input.key_down(Key::HardDrop);
+        input.update(&mut game);
+        assert_eq!(game.current_tetromino().orientation, Orientation::North);
+        assert_eq!(game.held_shape(), None);
+        let current = game.current_tetromino().shape;
+        let next = game.preview(2);
+        input.key_down(Key::Hold);
+        input.key_down(Key::HardDrop);
+        input.update(&mut game);
+        assert_eq!(game.held_shape(), Some(current));
+        assert_eq!(game.current_tetromino().shape, next[1]);
+        input.key_down(Key::HardDrop);
+        input.update(&mut game);
+        assert_eq!(game.held_shape(), Some(current));
+    }
+
+    #[test]
+    fn test_hold_is_buffered_during_entry_delay() {
+        let mut game = Game::with_ruleset(Ruleset {
+            entry_delay: 2,
+            ..Ruleset::default()
+        });
+        let mut input = InputHandler::new(Handling::default());
+        input.key_down(Key::HardDrop);
+        input.update(&mut game);
+        let next = game.preview(1)[0];
+        input.key_down(Key::Hold);
+        input.update(&mut game);
+        assert_eq!(game.held_shape(), None);
+        while *game.phase() != Phase::Falling {
+            game.tick();
+        }
+        assert_eq!(game.held_shape(), Some(next));
+        game.hard_drop();
+        while *game.phase() != Phase::Falling {
+            game.tick();
+        }
+        assert_eq!(game.held_shape(), Some(next));
+    }
+
+    #[test]
+    fn test_opposite_rotation_stays_buffered() {
+        let mut game = Game::new();
+        let mut input = InputHandler::new(Handling::default());
+        input.key_down(Key::RotateClockwise);
+        input.key_down(Key::RotateCounterClockwise);
+        input.key_up(Key::RotateClockwise);
+        input.key_down(Key::HardDrop);
+        input.update(&mut game);
+        assert_eq!(game.current_tetromino().orientation, Orientation::West);
+    }
+}
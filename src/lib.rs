@@ -0,0 +1,26 @@
+pub mod attack;
+pub mod board;
+pub mod bot;
+pub mod env;
+pub mod event;
+pub mod finesse;
+pub mod game;
+pub mod garbage;
+pub mod input;
+pub mod mode;
+pub mod movegen;
+pub mod net;
+pub mod opener;
+pub mod perfect_clear;
+pub mod position;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod randombag;
+pub mod ruleset;
+pub mod scoring;
+pub mod sim;
+pub mod spectator;
+pub mod stats;
+pub mod tetromino;
+pub mod tuner;
+pub mod versus;
@@ -1,7 +1 @@
-mod board;
-mod game;
-mod position;
-mod randombag;
-mod tetromino;
-
 fn main() {}
@@ -0,0 +1,81 @@
+use crate::game::{Game, GRAVITY_UNIT};
+
+pub mod dig;
+pub mod marathon;
+pub mod sprint;
+pub mod ultra;
+
+pub const TICKS_PER_SECOND: u64 = 60;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ModeState {
+    Playing,
+    /// The goal of the mode was reached.
+    Finished,
+    /// The player topped out before reaching the goal.
+    GameOver,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ModeResult {
+    pub state: ModeState,
+    pub score: u32,
+    pub lines: usize,
+    pub ticks: u64,
+}
+
+impl ModeResult {
+    pub fn seconds(&self) -> f64 {
+        ticks_to_seconds(self.ticks)
+    }
+}
+
+/// A mode wraps a `Game` and owns its goal and end conditions. Player input
+/// is applied to `game_mut` between calls to `tick`.
+pub trait Mode {
+    fn game(&self) -> &Game;
+    fn game_mut(&mut self) -> &mut Game;
+    /// Advances the game by one tick and checks the end conditions. Does
+    /// nothing once the mode has ended.
+    fn tick(&mut self) -> ModeState;
+    fn state(&self) -> ModeState;
+    fn result(&self) -> ModeResult;
+}
+
+pub fn ticks_to_seconds(ticks: u64) -> f64 {
+    ticks as f64 / TICKS_PER_SECOND as f64
+}
+
+/// Guideline gravity for a level, in 1/GRAVITY_UNIT rows per tick.
+pub fn level_gravity(level: u32) -> u32 {
+    let level = level.clamp(1, 20) as f64;
+    let seconds_per_row = (0.8 - (level - 1.0) * 0.007).powf(level - 1.0);
+    let rows_per_tick = 1.0 / (seconds_per_row * TICKS_PER_SECOND as f64);
+    (rows_per_tick * GRAVITY_UNIT as f64).round() as u32
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::tetromino::Shape;
+
+    /// Stacks ten vertical I pieces, clearing the bottom four lines of an
+    /// empty board.
+    pub(crate) fn clear_four_lines(game: &mut Game) {
+        for column in 0..10 {
+            game.spawn_specific(Shape::I);
+            game.rotate_clockwise();
+            while game.current_tetromino().position.x > column && game.move_left() {}
+            while game.current_tetromino().position.x < column && game.move_right() {}
+            game.hard_drop();
+        }
+    }
+
+    #[test]
+    fn test_level_gravity() {
+        assert_eq!(level_gravity(1), GRAVITY_UNIT / 60);
+        assert!(level_gravity(2) > level_gravity(1));
+        assert!(level_gravity(20) > 20 * GRAVITY_UNIT);
+        assert_eq!(level_gravity(25), level_gravity(20));
+    }
+}
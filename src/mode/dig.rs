@@ -0,0 +1,168 @@
+use crate::game::Game;
+use crate::garbage::GarbageGenerator;
+use crate::mode::{level_gravity, Mode, ModeResult, ModeState};
+
+/// Cheese race: dig through `total_lines` lines of garbage. At most
+/// `visible_lines` of them are on the board at once, the rest are added as
+/// the player clears them.
+pub struct Dig {
+    game: Game,
+    garbage: GarbageGenerator,
+    total_lines: usize,
+    visible_lines: usize,
+    lines_added: usize,
+    ticks: u64,
+    state: ModeState,
+}
+
+impl Dig {
+    /// With no `visible_lines` no garbage can ever be dug, so the race has
+    /// nothing to dig and is finished before the first tick, as it is with
+    /// no `total_lines`.
+    pub fn new(total_lines: usize, visible_lines: usize, messiness: f64) -> Self {
+        let game = Game::new();
+        let garbage = GarbageGenerator::new(messiness, game.board().width());
+        Self::with_garbage(game, garbage, total_lines, visible_lines)
+    }
+
+    fn with_garbage(
+        mut game: Game,
+        garbage: GarbageGenerator,
+        total_lines: usize,
+        visible_lines: usize,
+    ) -> Self {
+        game.set_gravity(level_gravity(1));
+        let total_lines = if visible_lines == 0 { 0 } else { total_lines };
+        let mut dig = Dig {
+            game,
+            garbage,
+            total_lines,
+            visible_lines,
+            lines_added: 0,
+            ticks: 0,
+            state: if total_lines == 0 {
+                ModeState::Finished
+            } else {
+                ModeState::Playing
+            },
+        };
+        dig.refill();
+        dig
+    }
+
+    /// Garbage lines the player has cleared so far.
+    pub fn garbage_cleared(&self) -> usize {
+        self.lines_added - self.game.board().garbage_lines()
+    }
+
+    pub fn garbage_remaining(&self) -> usize {
+        self.total_lines - self.garbage_cleared()
+    }
+
+    pub fn garbage_cleared_per_minute(&self) -> f64 {
+        let minutes = self.result().seconds() / 60.0;
+        if minutes == 0.0 {
+            return 0.0;
+        }
+        self.garbage_cleared() as f64 / minutes
+    }
+
+    fn refill(&mut self) -> bool {
+        let mut fits = true;
+        while self.game.board().garbage_lines() < self.visible_lines
+            && self.lines_added < self.total_lines
+        {
+            let hole = self.garbage.next_hole();
+            fits &= self.game.push_garbage(1, hole);
+            self.lines_added += 1;
+        }
+        fits
+    }
+}
+
+impl Mode for Dig {
+    fn game(&self) -> &Game {
+        &self.game
+    }
+
+    fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    fn tick(&mut self) -> ModeState {
+        if self.state != ModeState::Playing {
+            return self.state;
+        }
+        self.ticks += 1;
+        if !self.game.tick() {
+            self.state = ModeState::GameOver;
+        } else if self.garbage_remaining() == 0 {
+            self.state = ModeState::Finished;
+        } else if !self.refill() {
+            self.state = ModeState::GameOver;
+        }
+        self.state
+    }
+
+    fn state(&self) -> ModeState {
+        self.state
+    }
+
+    fn result(&self) -> ModeResult {
+        ModeResult {
+            state: self.state,
+            score: self.game.score(),
+            lines: self.game.lines_removed(),
+            ticks: self.ticks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+    use crate::tetromino::Shape;
+
+    fn fill_hole(game: &mut Game) {
+        let board = game.board();
+        let hole = (0..board.width())
+            .find(|x| board.is_free(Position::new(*x as i32, 0)))
+            .unwrap() as i32;
+        game.spawn_specific(Shape::I);
+        game.rotate_clockwise();
+        while game.current_tetromino().position.x > hole && game.move_left() {}
+        while game.current_tetromino().position.x < hole && game.move_right() {}
+        game.hard_drop();
+    }
+
+    #[test]
+    fn test_dig() {
+        let garbage = GarbageGenerator::with_seed(0.0, 10, 3);
+        let mut dig = Dig::with_garbage(Game::new(), garbage, 6, 4);
+        assert_eq!(dig.game().board().garbage_lines(), 4);
+        assert_eq!(dig.garbage_remaining(), 6);
+
+        fill_hole(dig.game_mut());
+        assert_eq!(dig.tick(), ModeState::Playing);
+        assert_eq!(dig.garbage_cleared(), 4);
+        assert_eq!(dig.game().board().garbage_lines(), 2);
+
+        fill_hole(dig.game_mut());
+        assert_eq!(dig.tick(), ModeState::Finished);
+        assert_eq!(dig.garbage_remaining(), 0);
+        assert_eq!(dig.garbage_cleared_per_minute(), 6.0 * 60.0 * 60.0 / 2.0);
+    }
+
+    #[test]
+    fn test_nothing_to_dig() {
+        for (total_lines, visible_lines) in [(10, 0), (0, 4)] {
+            let mut dig = Dig::new(total_lines, visible_lines, 0.0);
+            assert_eq!(dig.state(), ModeState::Finished);
+            assert_eq!(dig.garbage_remaining(), 0);
+            assert_eq!(dig.game().board().garbage_lines(), 0);
+            assert_eq!(dig.tick(), ModeState::Finished);
+            assert_eq!(dig.result().ticks, 0);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::game::Game;
+use crate::mode::{level_gravity, Mode, ModeResult, ModeState};
+
+pub const MARATHON_LINES: usize = 150;
+pub const MAX_LEVEL: u32 = 20;
+const LINES_PER_LEVEL: usize = 10;
+
+/// Clear lines while the level, and with it gravity, goes up every ten
+/// lines. Ends after `line_goal` lines, or never if there is no goal.
+pub struct Marathon {
+    game: Game,
+    start_level: u32,
+    line_goal: Option<usize>,
+    ticks: u64,
+    state: ModeState,
+}
+
+impl Marathon {
+    /// A `start_level` above `MAX_LEVEL` is clamped to it.
+    pub fn new(start_level: u32, line_goal: Option<usize>) -> Self {
+        let start_level = start_level.min(MAX_LEVEL);
+        let mut game = Game::new();
+        game.set_level(start_level);
+        game.set_gravity(level_gravity(start_level));
+        Marathon {
+            game,
+            start_level,
+            line_goal,
+            ticks: 0,
+            state: ModeState::Playing,
+        }
+    }
+
+    fn update_level(&mut self) {
+        let level = level_for_lines(self.start_level, self.game.lines_removed());
+        if level != self.game.level() {
+            self.game.set_level(level);
+            self.game.set_gravity(level_gravity(level));
+        }
+    }
+}
+
+impl Mode for Marathon {
+    fn game(&self) -> &Game {
+        &self.game
+    }
+
+    fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    fn tick(&mut self) -> ModeState {
+        if self.state != ModeState::Playing {
+            return self.state;
+        }
+        self.ticks += 1;
+        if !self.game.tick() {
+            self.state = ModeState::GameOver;
+        } else if self
+            .line_goal
+            .is_some_and(|goal| self.game.lines_removed() >= goal)
+        {
+            self.state = ModeState::Finished;
+        } else {
+            self.update_level();
+        }
+        self.state
+    }
+
+    fn state(&self) -> ModeState {
+        self.state
+    }
+
+    fn result(&self) -> ModeResult {
+        ModeResult {
+            state: self.state,
+            score: self.game.score(),
+            lines: self.game.lines_removed(),
+            ticks: self.ticks,
+        }
+    }
+}
+
+fn level_for_lines(start_level: u32, lines: usize) -> u32 {
+    (start_level + (lines / LINES_PER_LEVEL) as u32).min(MAX_LEVEL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::tests::clear_four_lines;
+
+    #[test]
+    fn test_level_for_lines() {
+        assert_eq!(level_for_lines(1, 0), 1);
+        assert_eq!(level_for_lines(1, 9), 1);
+        assert_eq!(level_for_lines(1, 10), 2);
+        assert_eq!(level_for_lines(5, 37), 8);
+        assert_eq!(level_for_lines(15, 1000), MAX_LEVEL);
+    }
+
+    #[test]
+    fn test_start_level() {
+        let marathon = Marathon::new(3, Some(MARATHON_LINES));
+        assert_eq!(marathon.game().level(), 3);
+        let mut marathon = Marathon::new(MAX_LEVEL + 5, None);
+        assert_eq!(marathon.game().level(), MAX_LEVEL);
+        marathon.tick();
+        assert_eq!(marathon.game().level(), MAX_LEVEL);
+    }
+
+    #[test]
+    fn test_line_goal() {
+        let mut marathon = Marathon::new(1, Some(8));
+        clear_four_lines(marathon.game_mut());
+        assert_eq!(marathon.tick(), ModeState::Playing);
+        clear_four_lines(marathon.game_mut());
+        assert_eq!(marathon.tick(), ModeState::Finished);
+        let result = marathon.result();
+        assert_eq!(result.lines, 8);
+        assert_eq!(result.ticks, 2);
+        assert_eq!(result.score, marathon.game().score());
+        assert_eq!(marathon.tick(), ModeState::Finished);
+        assert_eq!(marathon.result().ticks, 2);
+    }
+
+    #[test]
+    fn test_endless_game_over() {
+        let mut marathon = Marathon::new(1, None);
+        while marathon.tick() == ModeState::Playing {
+            marathon.game_mut().hard_drop();
+        }
+        assert_eq!(marathon.state(), ModeState::GameOver);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::game::Game;
+use crate::mode::{level_gravity, ticks_to_seconds, Mode, ModeResult, ModeState};
+use std::fmt;
+use std::str::FromStr;
+
+pub const SPRINT_LINES: usize = 40;
+const LINES_PER_SPLIT: usize = 10;
+
+/// Clear a number of lines, 40 by default, as fast as possible. The time at
+/// which every ten lines were reached is recorded as a split.
+pub struct Sprint {
+    game: Game,
+    target: usize,
+    ticks: u64,
+    splits: Vec<u64>,
+    state: ModeState,
+}
+
+impl Sprint {
+    pub fn new(target: usize) -> Self {
+        let mut game = Game::new();
+        game.set_gravity(level_gravity(1));
+        Sprint {
+            game,
+            target,
+            ticks: 0,
+            splits: Vec::new(),
+            state: ModeState::Playing,
+        }
+    }
+
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    /// Ticks at which 10, 20, 30, ... lines were reached.
+    pub fn splits(&self) -> &[u64] {
+        &self.splits
+    }
+
+    pub fn sprint_result(&self) -> SprintResult {
+        SprintResult {
+            mode: self.result(),
+            splits: self.splits.clone(),
+            pieces: self.game.pieces_locked(),
+            key_presses: self.game.key_presses(),
+        }
+    }
+
+    fn update_splits(&mut self) {
+        let lines = self.game.lines_removed().min(self.target);
+        while (self.splits.len() + 1) * LINES_PER_SPLIT <= lines {
+            self.splits.push(self.ticks);
+        }
+    }
+}
+
+impl Default for Sprint {
+    fn default() -> Self {
+        Self::new(SPRINT_LINES)
+    }
+}
+
+impl Mode for Sprint {
+    fn game(&self) -> &Game {
+        &self.game
+    }
+
+    fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    fn tick(&mut self) -> ModeState {
+        if self.state != ModeState::Playing {
+            return self.state;
+        }
+        self.ticks += 1;
+        if !self.game.tick() {
+            self.state = ModeState::GameOver;
+            return self.state;
+        }
+        self.update_splits();
+        if self.game.lines_removed() >= self.target {
+            self.state = ModeState::Finished;
+        }
+        self.state
+    }
+
+    fn state(&self) -> ModeState {
+        self.state
+    }
+
+    fn result(&self) -> ModeResult {
+        ModeResult {
+            state: self.state,
+            score: self.game.score(),
+            lines: self.game.lines_removed(),
+            ticks: self.ticks,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SprintResult {
+    pub mode: ModeResult,
+    pub splits: Vec<u64>,
+    pub pieces: usize,
+    pub key_presses: usize,
+}
+
+impl SprintResult {
+    pub fn pieces_per_second(&self) -> f64 {
+        let seconds = self.mode.seconds();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        self.pieces as f64 / seconds
+    }
+
+    pub fn keys_per_piece(&self) -> f64 {
+        if self.pieces == 0 {
+            return 0.0;
+        }
+        self.key_presses as f64 / self.pieces as f64
+    }
+
+    /// The record to store if this run was finished.
+    pub fn record(&self) -> Option<SprintRecord> {
+        if self.mode.state != ModeState::Finished {
+            return None;
+        }
+        Some(SprintRecord {
+            ticks: self.mode.ticks,
+            splits: self.splits.clone(),
+        })
+    }
+
+    /// Compares this run with a stored record. Negative deltas are ahead of
+    /// the record.
+    pub fn compare(&self, best: &SprintRecord) -> SprintComparison {
+        SprintComparison {
+            delta: self.mode.ticks as i64 - best.ticks as i64,
+            split_deltas: self
+                .splits
+                .iter()
+                .zip(best.splits.iter())
+                .map(|(split, best_split)| *split as i64 - *best_split as i64)
+                .collect(),
+        }
+    }
+
+    pub fn is_new_best(&self, best: Option<&SprintRecord>) -> bool {
+        match (self.mode.state, best) {
+            (ModeState::Finished, Some(best)) => self.mode.ticks < best.ticks,
+            (ModeState::Finished, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A finished sprint, stored as the personal best. The text form is the
+/// final time followed by the splits, all in ticks and separated by spaces.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SprintRecord {
+    pub ticks: u64,
+    pub splits: Vec<u64>,
+}
+
+impl SprintRecord {
+    pub fn seconds(&self) -> f64 {
+        ticks_to_seconds(self.ticks)
+    }
+}
+
+impl fmt::Display for SprintRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.ticks)?;
+        for split in &self.splits {
+            write!(f, " {}", split)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SprintRecord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = s.split_whitespace().map(|value| {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("invalid sprint time: {}", value))
+        });
+        let ticks = values
+            .next()
+            .unwrap_or_else(|| Err("empty sprint record".to_string()))?;
+        let splits = values.collect::<Result<Vec<u64>, _>>()?;
+        Ok(SprintRecord { ticks, splits })
+    }
+}
+
+/// Differences to a record in ticks.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SprintComparison {
+    pub delta: i64,
+    pub split_deltas: Vec<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::tests::clear_four_lines;
+
+    #[test]
+    fn test_sprint_splits() {
+        let mut sprint = Sprint::new(20);
+        for _ in 0..5 {
+            for _ in 0..30 {
+                sprint.tick();
+            }
+            clear_four_lines(sprint.game_mut());
+        }
+        assert_eq!(sprint.tick(), ModeState::Finished);
+        assert_eq!(sprint.splits(), &[91, 151]);
+        let result = sprint.sprint_result();
+        assert_eq!(result.mode.ticks, 151);
+        assert_eq!(result.mode.lines, 20);
+        assert_eq!(result.pieces, 50);
+        assert!((result.pieces_per_second() - 50.0 / (151.0 / 60.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_compare_with_record() {
+        let result = SprintResult {
+            mode: ModeResult {
+                state: ModeState::Finished,
+                score: 0,
+                lines: 20,
+                ticks: 1000,
+            },
+            splits: vec![400, 1000],
+            pieces: 50,
+            key_presses: 150,
+        };
+        let best = SprintRecord {
+            ticks: 1100,
+            splits: vec![380, 1100],
+        };
+        let comparison = result.compare(&best);
+        assert_eq!(comparison.delta, -100);
+        assert_eq!(comparison.split_deltas, vec![20, -100]);
+        assert!(result.is_new_best(Some(&best)));
+        assert!(!result.is_new_best(Some(&result.record().unwrap())));
+        assert_eq!(result.keys_per_piece(), 3.0);
+    }
+
+    #[test]
+    fn test_record_text_form() {
+        let record = SprintRecord {
+            ticks: 3600,
+            splits: vec![900, 1800, 2700, 3600],
+        };
+        assert_eq!(record.to_string(), "3600 900 1800 2700 3600");
+        assert_eq!("3600 900 1800 2700 3600".parse(), Ok(record));
+        assert_eq!(
+            "".parse::<SprintRecord>(),
+            Err("empty sprint record".to_string())
+        );
+        assert_eq!(
+            "12 x".parse::<SprintRecord>(),
+            Err("invalid sprint time: x".to_string())
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use crate::game::Game;
+use crate::mode::{level_gravity, ticks_to_seconds, Mode, ModeResult, ModeState, TICKS_PER_SECOND};
+
+pub const ULTRA_TWO_MINUTES: u64 = 2 * 60 * TICKS_PER_SECOND;
+pub const ULTRA_THREE_MINUTES: u64 = 3 * 60 * TICKS_PER_SECOND;
+
+/// Score as much as possible before the time runs out. Gravity stays at
+/// level one for the whole game.
+pub struct Ultra {
+    game: Game,
+    duration: u64,
+    ticks: u64,
+    score_graph: Vec<u32>,
+    state: ModeState,
+}
+
+impl Ultra {
+    /// Creates an Ultra game lasting `duration` ticks. With no time at all
+    /// it is finished before the first tick.
+    pub fn new(duration: u64) -> Self {
+        let mut game = Game::new();
+        game.set_gravity(level_gravity(1));
+        Ultra {
+            game,
+            duration,
+            ticks: 0,
+            score_graph: Vec::new(),
+            state: if duration == 0 {
+                ModeState::Finished
+            } else {
+                ModeState::Playing
+            },
+        }
+    }
+
+    pub fn remaining_ticks(&self) -> u64 {
+        self.duration.saturating_sub(self.ticks)
+    }
+
+    pub fn remaining_seconds(&self) -> f64 {
+        ticks_to_seconds(self.remaining_ticks())
+    }
+
+    /// The score at the end of every full second played.
+    pub fn score_graph(&self) -> &[u32] {
+        &self.score_graph
+    }
+
+    /// Lines of garbage the clears of this game were worth.
+    pub fn attack(&self) -> u32 {
+        self.game.attack_sent()
+    }
+}
+
+impl Default for Ultra {
+    fn default() -> Self {
+        Self::new(ULTRA_THREE_MINUTES)
+    }
+}
+
+impl Mode for Ultra {
+    fn game(&self) -> &Game {
+        &self.game
+    }
+
+    fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    fn tick(&mut self) -> ModeState {
+        if self.state != ModeState::Playing {
+            return self.state;
+        }
+        self.ticks += 1;
+        if !self.game.tick() {
+            self.state = ModeState::GameOver;
+            return self.state;
+        }
+        if self.ticks.is_multiple_of(TICKS_PER_SECOND) {
+            self.score_graph.push(self.game.score());
+        }
+        if self.ticks >= self.duration {
+            self.state = ModeState::Finished;
+        }
+        self.state
+    }
+
+    fn state(&self) -> ModeState {
+        self.state
+    }
+
+    fn result(&self) -> ModeResult {
+        ModeResult {
+            state: self.state,
+            score: self.game.score(),
+            lines: self.game.lines_removed(),
+            ticks: self.ticks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::tests::clear_four_lines;
+
+    #[test]
+    fn test_ultra_ends_at_time_zero() {
+        let mut ultra = Ultra::new(3 * TICKS_PER_SECOND);
+        assert_eq!(ultra.remaining_seconds(), 3.0);
+        for _ in 0..TICKS_PER_SECOND {
+            ultra.tick();
+        }
+        clear_four_lines(ultra.game_mut());
+        let score = ultra.game().score();
+        for _ in 0..2 * TICKS_PER_SECOND - 1 {
+            assert_eq!(ultra.tick(), ModeState::Playing);
+        }
+        assert_eq!(ultra.remaining_ticks(), 1);
+        assert_eq!(ultra.tick(), ModeState::Finished);
+        assert_eq!(ultra.remaining_ticks(), 0);
+        assert_eq!(ultra.score_graph(), &[0, score, score]);
+        assert_eq!(ultra.attack(), 4);
+        let result = ultra.result();
+        assert_eq!(result.lines, 4);
+        assert_eq!(result.seconds(), 3.0);
+    }
+
+    #[test]
+    fn test_zero_duration() {
+        let mut ultra = Ultra::new(0);
+        assert_eq!(ultra.remaining_ticks(), 0);
+        assert_eq!(ultra.state(), ModeState::Finished);
+        assert_eq!(ultra.tick(), ModeState::Finished);
+        assert_eq!(ultra.result().ticks, 0);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::input::{Handling, InputHandler};
+use crate::versus::{Versus, VersusSettings, VersusState};
+use std::fmt;
+use std::str::FromStr;
+
+pub mod client;
+pub mod protocol;
+pub mod server;
+
+use protocol::KeyEvent;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum MatchKind {
+    /// First to clear 40 lines wins, no garbage is sent.
+    Race,
+    /// Clears send garbage, the last player standing wins.
+    Battle,
+}
+
+impl MatchKind {
+    pub fn settings(&self) -> VersusSettings {
+        match self {
+            MatchKind::Race => VersusSettings::race(),
+            MatchKind::Battle => VersusSettings::default(),
+        }
+    }
+}
+
+impl fmt::Display for MatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatchKind::Race => write!(f, "race"),
+            MatchKind::Battle => write!(f, "battle"),
+        }
+    }
+}
+
+impl FromStr for MatchKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "race" => Ok(MatchKind::Race),
+            "battle" => Ok(MatchKind::Battle),
+            _ => Err(format!("unknown match kind: {}", s)),
+        }
+    }
+}
+
+/// The shared simulation of a networked match. The server and every client
+/// run one and feed it the same key events, so they stay in sync without
+/// sending boards around.
+pub struct Lockstep {
+    versus: Versus,
+    inputs: Vec<InputHandler>,
+    frame: u64,
+}
+
+impl Lockstep {
+    pub fn new(kind: MatchKind, players: usize, seed: u64) -> Self {
+        Lockstep {
+            versus: Versus::with_seed(kind.settings(), players, seed),
+            inputs: (0..players)
+                .map(|_| InputHandler::new(Handling::default()))
+                .collect(),
+            frame: 0,
+        }
+    }
+
+    /// The next frame to be applied.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn versus(&self) -> &Versus {
+        &self.versus
+    }
+
+    /// The hash of a player's board, compared to detect desyncs.
+    pub fn hash(&self, player: usize) -> u64 {
+        self.versus.game(player).board().hash()
+    }
+
+    pub fn forfeit(&mut self, player: usize) {
+        self.versus.forfeit(player);
+    }
+
+    /// Applies one frame of key events, one list per player, and ticks.
+    pub fn apply_frame(&mut self, events: &[Vec<KeyEvent>]) -> VersusState {
+        for (player, input) in self.inputs.iter_mut().enumerate() {
+            if !self.versus.is_alive(player) {
+                continue;
+            }
+            for (key, down) in events.get(player).into_iter().flatten() {
+                if *down {
+                    input.key_down(*key);
+                } else {
+                    input.key_up(*key);
+                }
+            }
+            input.update(self.versus.game_mut(player));
+        }
+        self.frame += 1;
+        self.versus.tick()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Key;
+
+    #[test]
+    fn test_lockstep_is_deterministic() {
+        let mut a = Lockstep::new(MatchKind::Battle, 2, 5);
+        let mut b = Lockstep::new(MatchKind::Battle, 2, 5);
+        for frame in 0..200 {
+            let events = vec![
+                vec![(Key::HardDrop, frame % 7 == 0)],
+                vec![(Key::Left, frame % 3 == 0), (Key::HardDrop, frame % 5 == 0)],
+            ];
+            a.apply_frame(&events);
+            b.apply_frame(&events);
+            assert_eq!(a.hash(0), b.hash(0));
+            assert_eq!(a.hash(1), b.hash(1));
+        }
+        assert_ne!(a.hash(0), a.hash(1));
+    }
+}
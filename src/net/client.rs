@@ -0,0 +1,127 @@
+use crate::game::Game;
+use crate::net::protocol::{KeyEvent, Message};
+use crate::net::server::{receive, send};
+use crate::net::Lockstep;
+use crate::versus::VersusState;
+use std::io::{self, BufReader};
+use std::net::{TcpStream, ToSocketAddrs};
+
+/// A player connected to a `Server`. Runs its own copy of the match, so
+/// the boards of all players are available locally.
+pub struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    player: usize,
+    lockstep: Lockstep,
+    result: Option<VersusState>,
+}
+
+impl Client {
+    pub fn connect<A: ToSocketAddrs>(address: A, name: &str) -> io::Result<Self> {
+        Self::join(TcpStream::connect(address)?, name)
+    }
+
+    /// Joins over an existing connection and waits until the match starts.
+    pub fn join(stream: TcpStream, name: &str) -> io::Result<Self> {
+        let mut writer = stream;
+        writer.set_nodelay(true)?;
+        let mut reader = BufReader::new(writer.try_clone()?);
+        let name = name.to_string();
+        send(&mut writer, &Message::Join { name })?;
+        match receive(&mut reader)? {
+            Some(Message::Welcome {
+                player,
+                players,
+                seed,
+                kind,
+            }) => Ok(Client {
+                reader,
+                writer,
+                player,
+                lockstep: Lockstep::new(kind, players, seed),
+                result: None,
+            }),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    pub fn lockstep(&self) -> &Lockstep {
+        &self.lockstep
+    }
+
+    pub fn game(&self) -> &Game {
+        self.lockstep.versus().game(self.player)
+    }
+
+    /// The final state once the server ended the match.
+    pub fn result(&self) -> Option<VersusState> {
+        self.result
+    }
+
+    /// Sends the key events for the next frame and waits until the server
+    /// relays the frame. Players that are out only wait.
+    pub fn step(&mut self, events: Vec<KeyEvent>) -> io::Result<VersusState> {
+        if let Some(result) = self.result {
+            return Ok(result);
+        }
+        if self.lockstep.versus().is_alive(self.player) {
+            let input = Message::Input {
+                frame: self.lockstep.frame(),
+                hash: self.lockstep.hash(self.player),
+                events,
+            };
+            send(&mut self.writer, &input)?;
+        }
+        loop {
+            match receive(&mut self.reader)? {
+                Some(Message::Desync { player, .. }) | Some(Message::Left { player }) => {
+                    self.lockstep.forfeit(player)
+                }
+                Some(Message::Frame { frame, events }) if frame == self.lockstep.frame() => {
+                    if self.lockstep.apply_frame(&events) == VersusState::Playing {
+                        return Ok(VersusState::Playing);
+                    }
+                }
+                Some(Message::End { winner }) => {
+                    let result = winner.map_or(VersusState::Draw, VersusState::Won);
+                    self.result = Some(result);
+                    return Ok(result);
+                }
+                _ => return Err(unexpected()),
+            }
+        }
+    }
+}
+
+fn unexpected() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "unexpected message")
+}
+
+/// A client without a display for bots and automated tests. `script` gets
+/// the frame number and the player's game and returns the key events for
+/// that frame.
+pub struct HeadlessClient<F> {
+    client: Client,
+    script: F,
+}
+
+impl<F: FnMut(u64, &Game) -> Vec<KeyEvent>> HeadlessClient<F> {
+    pub fn new(client: Client, script: F) -> Self {
+        HeadlessClient { client, script }
+    }
+
+    /// Plays until the server ends the match.
+    pub fn run(mut self) -> io::Result<VersusState> {
+        loop {
+            let events = (self.script)(self.client.lockstep().frame(), self.client.game());
+            match self.client.step(events)? {
+                VersusState::Playing => (),
+                state => return Ok(state),
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::input::Key;
+use crate::net::MatchKind;
+use std::fmt;
+use std::str::FromStr;
+
+/// A key going down (`true`) or up (`false`).
+pub type KeyEvent = (Key, bool);
+
+/// Messages are sent as single lines of text.
+///
+/// Clients send `Join` once and then one `Input` per frame. The server
+/// answers with `Welcome` and then relays everyone's input with one `Frame`
+/// per frame. `Desync` and `Left` are sent before the `Frame` they apply to.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Message {
+    Join {
+        name: String,
+    },
+    Welcome {
+        player: usize,
+        players: usize,
+        seed: u64,
+        kind: MatchKind,
+    },
+    /// The key events of a frame along with the hash of the player's board
+    /// before the frame.
+    Input {
+        frame: u64,
+        hash: u64,
+        events: Vec<KeyEvent>,
+    },
+    /// The key events of every player for a frame.
+    Frame {
+        frame: u64,
+        events: Vec<Vec<KeyEvent>>,
+    },
+    /// A player's board hash did not match the server. The player forfeits.
+    Desync {
+        frame: u64,
+        player: usize,
+    },
+    /// A player disconnected or broke the protocol. The player forfeits.
+    Left {
+        player: usize,
+    },
+    /// The match is over. `None` is a draw.
+    End {
+        winner: Option<usize>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseMessageError(String);
+
+impl fmt::Display for ParseMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid message: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseMessageError {}
+
+fn key_code(key: Key) -> char {
+    match key {
+        Key::Left => 'L',
+        Key::Right => 'R',
+        Key::SoftDrop => 'S',
+        Key::HardDrop => 'H',
+        Key::RotateClockwise => 'C',
+        Key::RotateCounterClockwise => 'W',
+        Key::Hold => 'O',
+    }
+}
+
+fn key_from_code(code: char) -> Option<Key> {
+    match code {
+        'L' => Some(Key::Left),
+        'R' => Some(Key::Right),
+        'S' => Some(Key::SoftDrop),
+        'H' => Some(Key::HardDrop),
+        'C' => Some(Key::RotateClockwise),
+        'W' => Some(Key::RotateCounterClockwise),
+        'O' => Some(Key::Hold),
+        _ => None,
+    }
+}
+
+/// Formats events like `+L,-L,+H`, or `.` if there are none.
+fn format_events(events: &[KeyEvent]) -> String {
+    if events.is_empty() {
+        return ".".to_string();
+    }
+    events
+        .iter()
+        .map(|(key, down)| format!("{}{}", if *down { '+' } else { '-' }, key_code(*key)))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn parse_events(s: &str) -> Option<Vec<KeyEvent>> {
+    if s == "." {
+        return Some(Vec::new());
+    }
+    s.split(',')
+        .map(|event| {
+            let mut chars = event.chars();
+            let down = match chars.next()? {
+                '+' => true,
+                '-' => false,
+                _ => return None,
+            };
+            let key = key_from_code(chars.next()?)?;
+            chars.next().is_none().then_some((key, down))
+        })
+        .collect()
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Join { name } => write!(f, "JOIN {}", name),
+            Message::Welcome {
+                player,
+                players,
+                seed,
+                kind,
+            } => write!(f, "WELCOME {} {} {} {}", player, players, seed, kind),
+            Message::Input {
+                frame,
+                hash,
+                events,
+            } => write!(f, "INPUT {} {:x} {}", frame, hash, format_events(events)),
+            Message::Frame { frame, events } => {
+                write!(f, "FRAME {}", frame)?;
+                for player_events in events {
+                    write!(f, " {}", format_events(player_events))?;
+                }
+                Ok(())
+            }
+            Message::Desync { frame, player } => write!(f, "DESYNC {} {}", frame, player),
+            Message::Left { player } => write!(f, "LEFT {}", player),
+            Message::End {
+                winner: Some(player),
+            } => write!(f, "END {}", player),
+            Message::End { winner: None } => write!(f, "END draw"),
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = ParseMessageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseMessageError(s.to_string());
+        let mut words = s.split_whitespace();
+        let mut next = || words.next().ok_or_else(error);
+        let message = match next()? {
+            "JOIN" => Message::Join {
+                name: s.trim()["JOIN".len()..].trim().to_string(),
+            },
+            "WELCOME" => Message::Welcome {
+                player: next()?.parse().map_err(|_| error())?,
+                players: next()?.parse().map_err(|_| error())?,
+                seed: next()?.parse().map_err(|_| error())?,
+                kind: next()?.parse().map_err(|_| error())?,
+            },
+            "INPUT" => Message::Input {
+                frame: next()?.parse().map_err(|_| error())?,
+                hash: u64::from_str_radix(next()?, 16).map_err(|_| error())?,
+                events: parse_events(next()?).ok_or_else(error)?,
+            },
+            "FRAME" => {
+                let frame = next()?.parse().map_err(|_| error())?;
+                let events = s
+                    .split_whitespace()
+                    .skip(2)
+                    .map(parse_events)
+                    .collect::<Option<Vec<Vec<KeyEvent>>>>()
+                    .ok_or_else(error)?;
+                return Ok(Message::Frame { frame, events });
+            }
+            "DESYNC" => Message::Desync {
+                frame: next()?.parse().map_err(|_| error())?,
+                player: next()?.parse().map_err(|_| error())?,
+            },
+            "LEFT" => Message::Left {
+                player: next()?.parse().map_err(|_| error())?,
+            },
+            "END" => Message::End {
+                winner: match next()? {
+                    "draw" => None,
+                    player => Some(player.parse().map_err(|_| error())?),
+                },
+            },
+            _ => return Err(error()),
+        };
+        match words.next() {
+            Some(_) if !matches!(message, Message::Join { .. }) => Err(error()),
+            _ => Ok(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(message: Message) {
+        assert_eq!(message.to_string().parse::<Message>(), Ok(message));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        roundtrip(Message::Join {
+            name: "two words".to_string(),
+        });
+        roundtrip(Message::Welcome {
+            player: 1,
+            players: 3,
+            seed: 12345,
+            kind: MatchKind::Race,
+        });
+        roundtrip(Message::Input {
+            frame: 7,
+            hash: u64::MAX,
+            events: vec![(Key::Left, true), (Key::Left, false), (Key::Hold, true)],
+        });
+        roundtrip(Message::Frame {
+            frame: 8,
+            events: vec![vec![], vec![(Key::RotateCounterClockwise, true)]],
+        });
+        roundtrip(Message::Desync {
+            frame: 9,
+            player: 0,
+        });
+        roundtrip(Message::Left { player: 2 });
+        roundtrip(Message::End { winner: Some(1) });
+        roundtrip(Message::End { winner: None });
+    }
+
+    #[test]
+    fn test_format() {
+        let message = Message::Input {
+            frame: 3,
+            hash: 255,
+            events: vec![(Key::HardDrop, true), (Key::SoftDrop, false)],
+        };
+        assert_eq!(message.to_string(), "INPUT 3 ff +H,-S");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!("".parse::<Message>().is_err());
+        assert!("HELLO".parse::<Message>().is_err());
+        assert!("INPUT 3 ff +X".parse::<Message>().is_err());
+        assert!("INPUT 3 ff +HH".parse::<Message>().is_err());
+        assert!("LEFT 1 2".parse::<Message>().is_err());
+        assert!("END".parse::<Message>().is_err());
+    }
+}
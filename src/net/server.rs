@@ -0,0 +1,283 @@
+use crate::net::protocol::{KeyEvent, Message};
+use crate::net::{Lockstep, MatchKind};
+use crate::versus::VersusState;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// How long the server waits for a message before dropping a player.
+pub const DEFAULT_INPUT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ServerConfig {
+    pub players: usize,
+    pub kind: MatchKind,
+    /// Shared by all players so everyone gets the same pieces.
+    pub seed: u64,
+    /// A player that sends nothing for this long counts as having left, so
+    /// the others can keep playing.
+    pub input_timeout: Duration,
+}
+
+/// Sends a message as a single line.
+pub(crate) fn send(stream: &mut TcpStream, message: &Message) -> io::Result<()> {
+    writeln!(stream, "{}", message)
+}
+
+/// Reads the next message, or `None` once the connection was closed.
+pub(crate) fn receive(reader: &mut BufReader<TcpStream>) -> io::Result<Option<Message>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    line.parse()
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    open: bool,
+}
+
+/// Runs a match between clients connecting over TCP. The server runs the
+/// authoritative simulation: every frame it waits for the input of all
+/// players still alive, checks their board hashes against its own and
+/// relays the inputs to everyone.
+pub struct Server {
+    listener: TcpListener,
+    config: ServerConfig,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(address: A, config: ServerConfig) -> io::Result<Self> {
+        Ok(Server {
+            listener: TcpListener::bind(address)?,
+            config,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Waits for all players to join and plays the match to the end.
+    pub fn run(self) -> io::Result<VersusState> {
+        let mut connections = self.accept_players()?;
+        for (player, connection) in connections.iter_mut().enumerate() {
+            let welcome = Message::Welcome {
+                player,
+                players: self.config.players,
+                seed: self.config.seed,
+                kind: self.config.kind,
+            };
+            connection.open = send(&mut connection.writer, &welcome).is_ok();
+        }
+        let mut lockstep = Lockstep::new(self.config.kind, self.config.players, self.config.seed);
+        loop {
+            let frame = lockstep.frame();
+            let mut events = vec![Vec::new(); self.config.players];
+            let mut forfeits = Vec::new();
+            for (player, connection) in connections.iter_mut().enumerate() {
+                if !lockstep.versus().is_alive(player) {
+                    continue;
+                }
+                match read_input(connection, frame) {
+                    Some((hash, _)) if hash != lockstep.hash(player) => {
+                        forfeits.push(Message::Desync { frame, player });
+                    }
+                    Some((_, input)) => events[player] = input,
+                    None => forfeits.push(Message::Left { player }),
+                }
+            }
+            for message in forfeits.iter() {
+                match message {
+                    Message::Desync { player, .. } | Message::Left { player } => {
+                        lockstep.forfeit(*player)
+                    }
+                    _ => unreachable!(),
+                }
+                broadcast(&mut connections, message);
+            }
+            if lockstep.versus().state() == VersusState::Playing {
+                lockstep.apply_frame(&events);
+                broadcast(&mut connections, &Message::Frame { frame, events });
+            }
+            let state = lockstep.versus().state();
+            let winner = match state {
+                VersusState::Playing => continue,
+                VersusState::Won(player) => Some(player),
+                VersusState::Draw => None,
+            };
+            broadcast(&mut connections, &Message::End { winner });
+            return Ok(state);
+        }
+    }
+
+    fn accept_players(&self) -> io::Result<Vec<Connection>> {
+        let mut connections = Vec::new();
+        while connections.len() < self.config.players {
+            let (stream, _) = self.listener.accept()?;
+            stream.set_nodelay(true)?;
+            // Also applies to the reader, which shares the socket.
+            stream.set_read_timeout(Some(self.config.input_timeout))?;
+            let mut reader = BufReader::new(stream.try_clone()?);
+            if let Ok(Some(Message::Join { .. })) = receive(&mut reader) {
+                connections.push(Connection {
+                    reader,
+                    writer: stream,
+                    open: true,
+                });
+            }
+        }
+        Ok(connections)
+    }
+}
+
+/// Reads a player's input for `frame`. Returns `None` if the connection was
+/// lost, timed out or the client sent anything else.
+fn read_input(connection: &mut Connection, frame: u64) -> Option<(u64, Vec<KeyEvent>)> {
+    if !connection.open {
+        return None;
+    }
+    match receive(&mut connection.reader) {
+        Ok(Some(Message::Input {
+            frame: input_frame,
+            hash,
+            events,
+        })) if input_frame == frame => Some((hash, events)),
+        _ => {
+            connection.open = false;
+            None
+        }
+    }
+}
+
+fn broadcast(connections: &mut [Connection], message: &Message) {
+    for connection in connections.iter_mut().filter(|connection| connection.open) {
+        connection.open = send(&mut connection.writer, message).is_ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Key;
+    use crate::net::client::{Client, HeadlessClient};
+    use std::thread;
+
+    fn start(players: usize, kind: MatchKind) -> (SocketAddr, thread::JoinHandle<VersusState>) {
+        let config = ServerConfig {
+            players,
+            kind,
+            seed: 42,
+            input_timeout: Duration::from_secs(2),
+        };
+        let server = Server::bind("127.0.0.1:0", config).unwrap();
+        let address = server.local_addr().unwrap();
+        (address, thread::spawn(move || server.run().unwrap()))
+    }
+
+    /// Hard drops every `interval` frames. Connects before returning so
+    /// players are numbered in the order they were created.
+    fn dropper(address: SocketAddr, name: &str, interval: u64) -> thread::JoinHandle<VersusState> {
+        let stream = TcpStream::connect(address).unwrap();
+        let name = name.to_string();
+        thread::spawn(move || {
+            let client = Client::join(stream, &name).unwrap();
+            HeadlessClient::new(client, move |frame, _| {
+                if frame % interval == 0 {
+                    vec![(Key::HardDrop, true), (Key::HardDrop, false)]
+                } else {
+                    Vec::new()
+                }
+            })
+            .run()
+            .unwrap()
+        })
+    }
+
+    #[test]
+    fn test_battle_on_localhost() {
+        let (address, server) = start(2, MatchKind::Battle);
+        let fast = dropper(address, "fast", 2);
+        let slow = dropper(address, "slow", 30);
+        let state = server.join().unwrap();
+        assert_eq!(state, VersusState::Won(1));
+        assert_eq!(fast.join().unwrap(), state);
+        assert_eq!(slow.join().unwrap(), state);
+    }
+
+    #[test]
+    fn test_disconnect_forfeits() {
+        let (address, server) = start(2, MatchKind::Race);
+        let player = dropper(address, "stays", 1000);
+        let mut leaving = TcpStream::connect(address).unwrap();
+        send(
+            &mut leaving,
+            &Message::Join {
+                name: "leaves".to_string(),
+            },
+        )
+        .unwrap();
+        drop(leaving);
+        assert_eq!(server.join().unwrap(), VersusState::Won(0));
+        assert_eq!(player.join().unwrap(), VersusState::Won(0));
+    }
+
+    #[test]
+    fn test_silent_player_times_out() {
+        let (address, server) = start(2, MatchKind::Race);
+        let player = dropper(address, "stays", 1000);
+        let mut silent = TcpStream::connect(address).unwrap();
+        send(
+            &mut silent,
+            &Message::Join {
+                name: "silent".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(server.join().unwrap(), VersusState::Won(0));
+        assert_eq!(player.join().unwrap(), VersusState::Won(0));
+        drop(silent);
+    }
+
+    #[test]
+    fn test_desync_is_detected() {
+        let (address, server) = start(2, MatchKind::Battle);
+        let player = dropper(address, "honest", 1000);
+        let mut stream = TcpStream::connect(address).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        send(
+            &mut stream,
+            &Message::Join {
+                name: "cheater".to_string(),
+            },
+        )
+        .unwrap();
+        assert!(matches!(
+            receive(&mut reader).unwrap(),
+            Some(Message::Welcome { player: 1, .. })
+        ));
+        let input = Message::Input {
+            frame: 0,
+            hash: 0,
+            events: Vec::new(),
+        };
+        send(&mut stream, &input).unwrap();
+        assert_eq!(
+            receive(&mut reader).unwrap(),
+            Some(Message::Desync {
+                frame: 0,
+                player: 1
+            })
+        );
+        assert_eq!(
+            receive(&mut reader).unwrap(),
+            Some(Message::End { winner: Some(0) })
+        );
+        assert_eq!(server.join().unwrap(), VersusState::Won(0));
+        assert_eq!(player.join().unwrap(), VersusState::Won(0));
+    }
+}
@@ -0,0 +1,471 @@
+use crate::board::Board;
+use crate::movegen::{moves, placement};
+use crate::perfect_clear::{choices, PcStep};
+use crate::position::Position;
+use crate::tetromino::{all_shapes, Orientation, Shape, Tetromino};
+use std::collections::HashSet;
+
+/// A well-known setup, described by the board it builds.
+#[derive(Debug, PartialEq)]
+pub struct Opener {
+    pub name: &'static str,
+    /// What to do once the setup is built.
+    pub follow_up: &'static str,
+    /// The setup from the top row down to the bottom row. Letters name the
+    /// shape that fills a cell and `.` stays empty. Neighbouring pieces of
+    /// the same shape are told apart by case.
+    pub rows: &'static [&'static str],
+    /// How many bags the setup's pieces come from, counted from the start
+    /// of the game.
+    pub bags: usize,
+}
+
+pub const OPENERS: &[Opener] = &[
+    Opener {
+        name: "TKI-3",
+        follow_up: "T-spin double with the first T",
+        rows: &[
+            ".......J..", //
+            "L..ZZ.SJJJ",
+            "L...ZZSSOO",
+            "LL.IIIISOO",
+        ],
+        bags: 1,
+    },
+    Opener {
+        name: "MKO stacking",
+        follow_up: "T-spin double on the right",
+        rows: &[
+            "..L.......", //
+            "LLLZ.SS..J",
+            "OOZZSS...J",
+            "OOZIIII.JJ",
+        ],
+        bags: 1,
+    },
+    Opener {
+        name: "C-spin",
+        follow_up: "T-spin triple under the roof",
+        rows: &[
+            "jjSS......", //
+            "jSS....TTT",
+            "jLL.ZZzzTJ",
+            "OOL..ZZzzJ",
+            "OOL.IIIIJJ",
+        ],
+        bags: 2,
+    },
+    Opener {
+        name: "DT cannon",
+        follow_up: "T-spin double, then a T-spin triple in the slot below",
+        rows: &[
+            "JJSS......", //
+            "JSS....jjj",
+            "JLlll...zj",
+            "ILlttt.zzT",
+            "ILL.toozTT",
+            "IOO..ooZZT",
+            "IOO.iiiiZZ",
+        ],
+        bags: 2,
+    },
+    Opener {
+        name: "PCO",
+        follow_up: "perfect clear with the held T and the next bag",
+        rows: &[
+            ".....S....", //
+            "JJ...SS...",
+            "JOOLLLSZZ.",
+            "JOOLIIIIZZ",
+        ],
+        bags: 1,
+    },
+];
+
+impl Opener {
+    /// The placements the setup is built from. Panics if the rows don't
+    /// describe tetrominoes.
+    pub fn pieces(&self) -> Vec<Tetromino> {
+        let height = self.rows.len() as i32;
+        let mut cells: Vec<(Position, char)> = Vec::new();
+        for (row, line) in self.rows.iter().enumerate() {
+            for (x, letter) in line.chars().enumerate() {
+                if letter != '.' {
+                    cells.push((Position::new(x as i32, height - 1 - row as i32), letter));
+                }
+            }
+        }
+        let mut pieces = Vec::new();
+        while let Some((start, letter)) = cells.pop() {
+            let mut piece = vec![start];
+            let mut index = 0;
+            while index < piece.len() {
+                let cell = piece[index];
+                cells.retain(|(other, other_letter)| {
+                    let touches = (other.x - cell.x).abs() + (other.y - cell.y).abs() == 1;
+                    if touches && *other_letter == letter {
+                        piece.push(*other);
+                        false
+                    } else {
+                        true
+                    }
+                });
+                index += 1;
+            }
+            let shape = shape_of(letter);
+            let tetromino = fit(shape, &piece).unwrap_or_else(|| {
+                panic!("{}: cells at {:?} are not a {:?}", self.name, start, shape)
+            });
+            pieces.push(tetromino);
+        }
+        pieces
+    }
+}
+
+fn shape_of(letter: char) -> Shape {
+    match letter.to_ascii_uppercase() {
+        'I' => Shape::I,
+        'J' => Shape::J,
+        'L' => Shape::L,
+        'O' => Shape::O,
+        'S' => Shape::S,
+        'T' => Shape::T,
+        'Z' => Shape::Z,
+        _ => panic!("unknown shape: {}", letter),
+    }
+}
+
+/// Finds the tetromino of `shape` that covers exactly `cells`.
+fn fit(shape: Shape, cells: &[Position]) -> Option<Tetromino> {
+    if cells.len() != 4 {
+        return None;
+    }
+    let mut sorted = cells.to_vec();
+    sorted.sort_by_key(|cell| (cell.y, cell.x));
+    let orientations = [
+        Orientation::North,
+        Orientation::East,
+        Orientation::South,
+        Orientation::West,
+    ];
+    orientations.into_iter().find_map(|orientation| {
+        let mut t = Tetromino::new(Position::new(0, 0), shape);
+        t.orientation = orientation;
+        cells
+            .iter()
+            .map(|cell| t.get_moved(*cell - t.blocks()[0]))
+            .find(|t| placement(*t).to_vec() == sorted)
+    })
+}
+
+/// How far a setup is from being built.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SetupProgress {
+    pub opener: &'static Opener,
+    pub placed: usize,
+    pub remaining: usize,
+    /// A placement that keeps the setup buildable with the visible pieces.
+    /// `None` once the setup is complete.
+    pub next: Option<PcStep>,
+    /// The share of the orders the rest of the bag may come in that still
+    /// finish the setup. `None` if the bag is not known.
+    pub chance: Option<f64>,
+}
+
+/// Lists the setups that can still be built on `board`. A setup can be
+/// built if the board holds nothing but some of its pieces, and the visible
+/// pieces can be placed in order, using hold, without giving up any of it.
+/// `bag` are the pieces left in the current bag after the queue. With it,
+/// setups that no order of them can finish are left out as well.
+pub fn recognize(
+    board: &Board,
+    current: Shape,
+    hold: Option<Shape>,
+    queue: &[Shape],
+    bag: Option<&[Shape]>,
+) -> Vec<SetupProgress> {
+    OPENERS
+        .iter()
+        .filter_map(|opener| {
+            let pieces = opener.pieces();
+            let filled = |t: &Tetromino| t.cells().iter().all(|cell| !board.is_free(*cell));
+            let (placed, remaining): (Vec<Tetromino>, Vec<Tetromino>) =
+                pieces.into_iter().partition(filled);
+            let untouched = remaining
+                .iter()
+                .all(|t| t.cells().iter().all(|cell| board.is_free(*cell)));
+            if !untouched || board.filled_cells() != placed.len() * 4 {
+                return None;
+            }
+            let chance = match bag {
+                Some(bag) => {
+                    let visible = (current, hold, queue);
+                    let chance =
+                        finish_chance(opener, board, placed.len(), visible, &remaining, bag);
+                    if chance == 0.0 {
+                        return None;
+                    }
+                    Some(chance)
+                }
+                None => None,
+            };
+            let next = advance(
+                board,
+                Some(current),
+                hold,
+                queue,
+                &remaining,
+                &mut HashSet::new(),
+            )?;
+            Some(SetupProgress {
+                opener,
+                placed: placed.len(),
+                remaining: remaining.len(),
+                next,
+                chance,
+            })
+        })
+        .collect()
+}
+
+/// The share of the orders the pieces left in the bag may come in that
+/// still finish the setup. Only bags the setup draws from matter. Every
+/// piece drawn so far is either on the board or visible.
+fn finish_chance(
+    opener: &Opener,
+    board: &Board,
+    placed: usize,
+    visible: (Shape, Option<Shape>, &[Shape]),
+    remaining: &[Tetromino],
+    bag: &[Shape],
+) -> f64 {
+    let (current, hold, queue) = visible;
+    let drawn = placed + 1 + hold.is_some() as usize + queue.len();
+    if drawn.div_ceil(all_shapes().len()) > opener.bags {
+        return 1.0;
+    }
+    // Placing the remaining pieces takes at most one more from the queue.
+    let unseen = (remaining.len() + 1).saturating_sub(queue.len());
+    let orders = orders(bag, unseen.min(bag.len()));
+    let mut dead = HashSet::new();
+    let finished = orders
+        .iter()
+        .filter(|order| {
+            let queue: Vec<Shape> = queue.iter().chain(order.iter()).copied().collect();
+            advance(board, Some(current), hold, &queue, remaining, &mut dead).is_some()
+        })
+        .count();
+    finished as f64 / orders.len() as f64
+}
+
+/// Every sequence of `length` of `shapes` that can come next.
+fn orders(shapes: &[Shape], length: usize) -> Vec<Vec<Shape>> {
+    if length == 0 {
+        return vec![Vec::new()];
+    }
+    let mut orders = Vec::new();
+    for index in 0..shapes.len() {
+        let mut rest = shapes.to_vec();
+        let first = rest.remove(index);
+        for mut order in self::orders(&rest, length - 1) {
+            order.insert(0, first);
+            orders.push(order);
+        }
+    }
+    orders
+}
+
+/// Board hash, current, hold and queue.
+type PositionKey = (u64, Option<Shape>, Option<Shape>, Vec<Shape>);
+
+/// Returns `None` if the visible pieces can't build the rest of the setup,
+/// and otherwise the first placement of a way to do it. `dead` collects the
+/// positions known not to.
+fn advance(
+    board: &Board,
+    current: Option<Shape>,
+    hold: Option<Shape>,
+    queue: &[Shape],
+    remaining: &[Tetromino],
+    dead: &mut HashSet<PositionKey>,
+) -> Option<Option<PcStep>> {
+    if remaining.is_empty() || current.is_none() {
+        return Some(None);
+    }
+    let key = (board.hash(), current, hold, queue.to_vec());
+    if dead.contains(&key) {
+        return None;
+    }
+    for (piece, from_hold, current, hold, queue) in choices(current, hold, queue) {
+        let reachable: Vec<_> = moves(board, piece)
+            .into_iter()
+            .map(|m| placement(m.tetromino))
+            .collect();
+        for (index, target) in remaining.iter().enumerate() {
+            if target.shape != piece || !reachable.contains(&placement(*target)) {
+                continue;
+            }
+            let mut next = board.clone();
+            next.put(target);
+            let mut rest = remaining.to_vec();
+            rest.remove(index);
+            if advance(&next, current, hold, queue, &rest, dead).is_some() {
+                return Some(Some(PcStep {
+                    hold: from_hold,
+                    tetromino: *target,
+                }));
+            }
+        }
+    }
+    dead.insert(key);
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::empty_board;
+    use crate::perfect_clear::{solve_all, PcBudget, PcQuery};
+    use crate::scoring::TSpin;
+
+    fn opener(name: &str) -> &'static Opener {
+        OPENERS.iter().find(|opener| opener.name == name).unwrap()
+    }
+
+    fn build(opener: &Opener) -> Board {
+        let mut board = empty_board();
+        for piece in opener.pieces() {
+            board.put(&piece);
+        }
+        board
+    }
+
+    #[test]
+    fn test_patterns_are_buildable() {
+        for opener in OPENERS {
+            let mut pieces = opener.pieces();
+            pieces.sort_by_key(|t| t.cells().iter().map(|cell| cell.y).min());
+            let shapes: Vec<Shape> = pieces.iter().map(|t| t.shape).collect();
+            let progress = recognize(&empty_board(), shapes[0], None, &shapes[1..], None);
+            assert!(
+                progress.iter().any(|p| p.opener == opener),
+                "{} can't be built bottom up",
+                opener.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_bags() {
+        for opener in OPENERS {
+            let pieces = opener.pieces();
+            for shape in all_shapes() {
+                let count = pieces.iter().filter(|t| t.shape == shape).count();
+                assert!(
+                    count <= opener.bags,
+                    "{} has {} {:?}",
+                    opener.name,
+                    count,
+                    shape
+                );
+            }
+            assert!(pieces.len() > (opener.bags - 1) * all_shapes().len());
+        }
+    }
+
+    /// The lines cleared by the best T-spin on `board`, and the board after.
+    fn tspin(board: &Board) -> Option<(usize, Board)> {
+        moves(board, Shape::T)
+            .into_iter()
+            .filter(|m| m.tspin(board) == TSpin::Full)
+            .map(|m| {
+                let mut next = board.clone();
+                next.put(&m.tetromino);
+                (next.remove_full_lines(), next)
+            })
+            .max_by_key(|(lines, _)| *lines)
+    }
+
+    #[test]
+    fn test_tspin_follow_ups() {
+        let follow_ups = [
+            ("TKI-3", 2),
+            ("MKO stacking", 2),
+            ("C-spin", 3),
+            ("DT cannon", 2),
+        ];
+        for (name, lines) in follow_ups {
+            let (cleared, _) = tspin(&build(opener(name))).unwrap();
+            assert_eq!(cleared, lines, "{}", name);
+        }
+        let (_, board) = tspin(&build(opener("DT cannon"))).unwrap();
+        assert_eq!(tspin(&board).unwrap().0, 3);
+    }
+
+    #[test]
+    fn test_pco_perfect_clear() {
+        let board = build(opener("PCO"));
+        let query = PcQuery {
+            board: &board,
+            current: Shape::T,
+            hold: None,
+            queue: &[Shape::I, Shape::L, Shape::T],
+            max_lines: 4,
+        };
+        assert!(!solve_all(&query, PcBudget::default()).solutions.is_empty());
+    }
+
+    #[test]
+    fn test_recognize() {
+        let tki = opener("TKI-3");
+        let i_piece = tki
+            .pieces()
+            .into_iter()
+            .find(|t| t.shape == Shape::I)
+            .unwrap();
+        let mut board = empty_board();
+        board.put(&i_piece);
+        let progress = recognize(&board, Shape::T, None, &[Shape::L, Shape::O], None);
+        let progress = progress.iter().find(|p| p.opener == tki).unwrap();
+        assert_eq!((progress.placed, progress.remaining), (1, 5));
+        let next = progress.next.unwrap();
+        assert!(next.hold);
+        assert_eq!(next.tetromino.shape, Shape::L);
+
+        let mut diverged = empty_board();
+        diverged.put(&i_piece.get_moved(Position::new(0, 1)));
+        assert!(recognize(&diverged, Shape::T, None, &[Shape::L], None)
+            .iter()
+            .all(|p| p.opener != tki));
+
+        let complete = recognize(&build(tki), Shape::T, None, &[], None);
+        let complete = complete.iter().find(|p| p.opener == tki).unwrap();
+        assert_eq!((complete.remaining, complete.next), (0, None));
+    }
+
+    #[test]
+    fn test_recognize_with_bag() {
+        let tki = opener("TKI-3");
+        let mut board = empty_board();
+        for piece in tki.pieces() {
+            if piece.shape == Shape::I {
+                board.put(&piece);
+            }
+        }
+        let progress = |queue: &[Shape], bag: Option<&[Shape]>| {
+            recognize(&board, Shape::T, None, queue, bag)
+                .into_iter()
+                .find(|p| p.opener == tki)
+        };
+        let chance = |queue: &[Shape], bag: &[Shape]| progress(queue, Some(bag)).map(|p| p.chance);
+        let queue = [Shape::Z, Shape::S, Shape::L];
+        assert_eq!(progress(&queue, None).unwrap().chance, None);
+        assert_eq!(chance(&queue, &[Shape::J, Shape::O]), Some(Some(0.5)));
+        let queue = [Shape::Z, Shape::O, Shape::S];
+        assert_eq!(chance(&queue, &[Shape::J, Shape::L]), Some(Some(1.0)));
+        // Whichever of L and O comes first, the setup can't be finished.
+        let queue = [Shape::Z, Shape::S, Shape::J];
+        assert!(progress(&queue, None).is_some());
+        assert_eq!(chance(&queue, &[Shape::L, Shape::O]), None);
+    }
+}
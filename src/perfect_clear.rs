@@ -0,0 +1,441 @@
+use crate::board::Board;
+use crate::movegen::moves;
+use crate::tetromino::{all_shapes, Shape, Tetromino};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// The position a perfect clear is searched from.
+#[derive(Copy, Clone)]
+pub struct PcQuery<'a> {
+    pub board: &'a Board,
+    pub current: Shape,
+    pub hold: Option<Shape>,
+    /// The visible next pieces.
+    pub queue: &'a [Shape],
+    /// The board has to be empty before more lines than this are used.
+    pub max_lines: usize,
+}
+
+/// Limits how long a search may take. The search stops at whichever limit
+/// is hit first.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct PcBudget {
+    pub max_nodes: usize,
+    pub max_time: Option<Duration>,
+}
+
+impl Default for PcBudget {
+    fn default() -> Self {
+        PcBudget {
+            max_nodes: 1_000_000,
+            max_time: None,
+        }
+    }
+}
+
+/// A single placement of a solution. `hold` means the piece was taken from
+/// hold, or from the queue if hold was empty, instead of being the current
+/// piece.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct PcStep {
+    pub hold: bool,
+    pub tetromino: Tetromino,
+}
+
+pub type PcSolution = Vec<PcStep>;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PcSearch {
+    pub solutions: Vec<PcSolution>,
+    pub nodes: usize,
+    /// Whether the budget ran out, so there may be more solutions.
+    pub exhausted: bool,
+}
+
+/// A solution and the chance that its first step still leads to a perfect
+/// clear once the unseen pieces are known.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PcChance {
+    /// Solves the queue extended by one possible sequence of unseen pieces,
+    /// so only the steps using visible pieces are certain.
+    pub solution: PcSolution,
+    /// The share of the searched sequences the first step solves.
+    pub probability: f64,
+    /// Whether the budget ran out before every sequence was searched.
+    pub exhausted: bool,
+}
+
+/// Finds all perfect clears that can be built from the visible pieces.
+pub fn solve_all(query: &PcQuery, budget: PcBudget) -> PcSearch {
+    let mut solver = Solver::new(budget);
+    solver.solve(query, query.queue);
+    PcSearch {
+        solutions: solver.solutions,
+        nodes: solver.nodes,
+        exhausted: solver.exhausted,
+    }
+}
+
+/// Finds the first step with the highest chance of leading to a perfect
+/// clear. When more pieces are needed than visible, every possible sequence
+/// of unseen pieces is tried. `bag` are the pieces left in the current bag
+/// after the queue; without it unseen pieces are assumed to be independent.
+/// The budget is shared by all sequences. Once it runs out the remaining
+/// ones are skipped and the probability only counts those searched in full.
+pub fn best_solution(query: &PcQuery, bag: Option<&[Shape]>, budget: PcBudget) -> Option<PcChance> {
+    let needed = pieces_needed(query.board, query.max_lines)?;
+    let available = 1 + query.queue.len() + query.hold.is_some() as usize;
+    // Hold lets one more piece than is placed pass through: the one left in
+    // hold at the end.
+    let unseen = (needed + 1).saturating_sub(available);
+    let mut solver = Solver::new(budget);
+    let mut first_steps: HashMap<PcStep, (usize, PcSolution)> = HashMap::new();
+    let mut searched = 0;
+    for_each_continuation(bag, unseen, &mut Vec::new(), &mut |sequence| {
+        let queue: Vec<Shape> = query.queue.iter().chain(sequence).copied().collect();
+        solver.solutions.clear();
+        solver.dead.clear();
+        solver.solve(query, &queue);
+        if solver.exhausted {
+            return false;
+        }
+        searched += 1;
+        let mut counted = HashSet::new();
+        for solution in solver.solutions.drain(..) {
+            if counted.insert(solution[0]) {
+                first_steps
+                    .entry(solution[0])
+                    .or_insert_with(|| (0, solution))
+                    .0 += 1;
+            }
+        }
+        true
+    });
+    first_steps
+        .into_values()
+        .max_by_key(|(count, _)| *count)
+        .map(|(count, solution)| PcChance {
+            solution,
+            probability: count as f64 / searched as f64,
+            exhausted: solver.exhausted,
+        })
+}
+
+/// The fewest pieces that can fill the lines needed for a perfect clear.
+fn pieces_needed(board: &Board, max_lines: usize) -> Option<usize> {
+    let filled = board.filled_cells();
+    (board.stack_height().max(1)..=max_lines)
+        .map(|lines| lines * board.width())
+        .find(|cells| *cells >= filled && (cells - filled).is_multiple_of(4))
+        .map(|cells| (cells - filled) / 4)
+}
+
+/// Visits all equally likely sequences of `length` unseen pieces one at a
+/// time, each appended to `sequence`, and stops as soon as `visit` returns
+/// false. Returns false if it stopped early.
+fn for_each_continuation(
+    bag: Option<&[Shape]>,
+    length: usize,
+    sequence: &mut Vec<Shape>,
+    visit: &mut impl FnMut(&[Shape]) -> bool,
+) -> bool {
+    if length == 0 {
+        return visit(sequence);
+    }
+    let remaining = match bag {
+        Some(bag) if !bag.is_empty() => bag.to_vec(),
+        _ => all_shapes(),
+    };
+    for (index, shape) in remaining.iter().enumerate() {
+        let mut rest = remaining.clone();
+        rest.remove(index);
+        let rest = bag.map(|_| rest);
+        sequence.push(*shape);
+        let finished = for_each_continuation(rest.as_deref(), length - 1, sequence, visit);
+        sequence.pop();
+        if !finished {
+            return false;
+        }
+    }
+    true
+}
+
+/// Board hash, current, hold, pieces queued and lines left.
+type PositionKey = (u64, Option<Shape>, Option<Shape>, usize, usize);
+
+struct Solver {
+    budget: PcBudget,
+    start: Instant,
+    nodes: usize,
+    exhausted: bool,
+    /// Positions known not to lead to a perfect clear.
+    dead: HashSet<PositionKey>,
+    solutions: Vec<PcSolution>,
+    path: Vec<PcStep>,
+}
+
+impl Solver {
+    fn new(budget: PcBudget) -> Self {
+        Solver {
+            budget,
+            start: Instant::now(),
+            nodes: 0,
+            exhausted: false,
+            dead: HashSet::new(),
+            solutions: Vec::new(),
+            path: Vec::new(),
+        }
+    }
+
+    fn solve(&mut self, query: &PcQuery, queue: &[Shape]) {
+        self.search(
+            query.board,
+            Some(query.current),
+            query.hold,
+            queue,
+            query.max_lines,
+        );
+    }
+
+    fn out_of_budget(&mut self) -> bool {
+        self.exhausted |= self.nodes >= self.budget.max_nodes
+            || self
+                .budget
+                .max_time
+                .is_some_and(|max_time| self.start.elapsed() >= max_time);
+        self.exhausted
+    }
+
+    /// Returns whether a perfect clear was found from this position.
+    fn search(
+        &mut self,
+        board: &Board,
+        current: Option<Shape>,
+        hold: Option<Shape>,
+        queue: &[Shape],
+        lines_left: usize,
+    ) -> bool {
+        if board.stack_height() == 0 && !self.path.is_empty() {
+            self.solutions.push(self.path.clone());
+            return true;
+        }
+        if self.out_of_budget() {
+            return false;
+        }
+        self.nodes += 1;
+        let available = current.is_some() as usize + hold.is_some() as usize + queue.len();
+        if pieces_needed(board, lines_left).is_none_or(|needed| needed > available) {
+            return false;
+        }
+        let key = (board.hash(), current, hold, queue.len(), lines_left);
+        if self.dead.contains(&key) {
+            return false;
+        }
+        let mut found = false;
+        for (piece, from_hold, current, hold, queue) in choices(current, hold, queue) {
+            for m in moves(board, piece) {
+                if m.tetromino
+                    .cells()
+                    .iter()
+                    .any(|cell| cell.y >= lines_left as i32)
+                {
+                    continue;
+                }
+                let mut next = board.clone();
+                next.put(&m.tetromino);
+                let cleared = next.remove_full_lines();
+                self.path.push(PcStep {
+                    hold: from_hold,
+                    tetromino: m.tetromino,
+                });
+                found |= self.search(&next, current, hold, queue, lines_left - cleared);
+                self.path.pop();
+            }
+        }
+        if !found && !self.exhausted {
+            self.dead.insert(key);
+        }
+        found
+    }
+}
+
+pub(crate) type Choice<'a> = (Shape, bool, Option<Shape>, Option<Shape>, &'a [Shape]);
+
+/// The pieces that can be placed next: the current one, or the one hold
+/// gives. Returns the piece, whether hold was used, and what is current,
+/// held and queued afterwards.
+pub(crate) fn choices<'a>(
+    current: Option<Shape>,
+    hold: Option<Shape>,
+    queue: &'a [Shape],
+) -> Vec<Choice<'a>> {
+    let next = |queue: &'a [Shape]| (queue.first().copied(), queue.get(1..).unwrap_or(&[]));
+    let mut choices = Vec::new();
+    match (current, hold) {
+        (Some(current), Some(held)) => {
+            let (after, rest) = next(queue);
+            choices.push((current, false, after, hold, rest));
+            if held != current {
+                choices.push((held, true, after, Some(current), rest));
+            }
+        }
+        (Some(current), None) => {
+            let (after, rest) = next(queue);
+            choices.push((current, false, after, None, rest));
+            if let Some(from_queue) = after {
+                let (after, rest) = next(rest);
+                choices.push((from_queue, true, after, Some(current), rest));
+            }
+        }
+        (None, Some(held)) => choices.push((held, true, None, None, queue)),
+        (None, None) => (),
+    }
+    choices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::empty_board;
+    use crate::board::tests::board_with_gaps;
+
+    #[test]
+    fn test_single_piece() {
+        let board = board_with_gaps(1, &[[3, 0], [4, 0], [5, 0], [6, 0]]);
+        let query = PcQuery {
+            board: &board,
+            current: Shape::I,
+            hold: None,
+            queue: &[],
+            max_lines: 4,
+        };
+        let search = solve_all(&query, PcBudget::default());
+        assert_eq!(search.solutions.len(), 1);
+        assert!(!search.solutions[0][0].hold);
+        assert!(!search.exhausted);
+    }
+
+    #[test]
+    fn test_uses_hold() {
+        let board = board_with_gaps(2, &[[4, 0], [5, 0], [4, 1], [5, 1]]);
+        let query = PcQuery {
+            board: &board,
+            current: Shape::T,
+            hold: None,
+            queue: &[Shape::O],
+            max_lines: 2,
+        };
+        let solutions = solve_all(&query, PcBudget::default()).solutions;
+        assert_eq!(solutions.len(), 1);
+        assert!(solutions[0][0].hold);
+        assert_eq!(solutions[0][0].tetromino.shape, Shape::O);
+    }
+
+    #[test]
+    fn test_two_line_perfect_clear() {
+        let gaps = [
+            [0, 0],
+            [1, 0],
+            [2, 0],
+            [3, 0],
+            [0, 1],
+            [1, 1],
+            [2, 1],
+            [3, 1],
+        ];
+        let board = board_with_gaps(2, &gaps);
+        let query = PcQuery {
+            board: &board,
+            current: Shape::L,
+            hold: None,
+            queue: &[Shape::L],
+            max_lines: 2,
+        };
+        let solutions = solve_all(&query, PcBudget::default()).solutions;
+        assert!(!solutions.is_empty());
+        assert!(solutions.iter().all(|solution| solution.len() == 2));
+    }
+
+    #[test]
+    fn test_budget() {
+        let board = empty_board();
+        let queue = [Shape::T, Shape::S, Shape::Z, Shape::L, Shape::J, Shape::O];
+        let query = PcQuery {
+            board: &board,
+            current: Shape::I,
+            hold: None,
+            queue: &queue,
+            max_lines: 4,
+        };
+        let budget = PcBudget {
+            max_nodes: 100,
+            max_time: None,
+        };
+        let search = solve_all(&query, budget);
+        assert!(search.exhausted);
+        assert_eq!(search.nodes, 100);
+    }
+
+    #[test]
+    fn test_continuations() {
+        let count = |bag: Option<&[Shape]>, length, limit| {
+            let mut visited = 0;
+            let finished = for_each_continuation(bag, length, &mut Vec::new(), &mut |_| {
+                visited += 1;
+                visited < limit
+            });
+            (visited, finished)
+        };
+        assert_eq!(count(None, 2, usize::MAX), (49, true));
+        assert_eq!(
+            count(Some(&[Shape::I, Shape::T]), 3, usize::MAX),
+            (14, true)
+        );
+        assert_eq!(count(None, 6, 3), (3, false));
+    }
+
+    #[test]
+    fn test_probability_over_unseen_pieces() {
+        let board = board_with_gaps(1, &[[3, 0], [4, 0], [5, 0], [6, 0]]);
+        let query = PcQuery {
+            board: &board,
+            current: Shape::O,
+            hold: None,
+            queue: &[],
+            max_lines: 4,
+        };
+        let bag = [Shape::I, Shape::T];
+        let chance = best_solution(&query, Some(&bag), PcBudget::default()).unwrap();
+        assert_eq!(chance.probability, 0.5);
+        assert_eq!(chance.solution[0].tetromino.shape, Shape::I);
+
+        let chance = best_solution(&query, None, PcBudget::default()).unwrap();
+        assert_eq!(chance.probability, 1.0 / 7.0);
+        assert!(!chance.exhausted);
+    }
+
+    #[test]
+    fn test_probability_with_exhausted_budget() {
+        let board = board_with_gaps(1, &[[3, 0], [4, 0], [5, 0], [6, 0]]);
+        let query = PcQuery {
+            board: &board,
+            current: Shape::O,
+            hold: None,
+            queue: &[],
+            max_lines: 4,
+        };
+        let search = |max_nodes| {
+            let budget = PcBudget {
+                max_nodes,
+                max_time: None,
+            };
+            best_solution(&query, Some(&[Shape::I, Shape::T]), budget)
+        };
+        let full = search(1_000_000).unwrap();
+        assert!(!full.exhausted);
+        let nodes = (1..1000).find(|nodes| search(*nodes).is_some()).unwrap();
+        let chance = search(nodes).unwrap();
+        assert!(chance.exhausted);
+        assert_eq!(chance.probability, 1.0);
+    }
+}
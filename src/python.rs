@@ -0,0 +1,309 @@
+//! Python bindings, built with the `python` feature. The module is named
+//! `tetris`; build it with maturin, which enables `extension-module`.
+
+use crate::board::{Board, BoardContent};
+use crate::game::{Game, Phase};
+use crate::input::{press, Key};
+use crate::position::Position;
+use crate::ruleset::Ruleset;
+use crate::tetromino::{Orientation, Shape, Tetromino};
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray2};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Board cells in arrays: 0 is empty, 1 to 7 are the shapes in the order of
+/// `Shape` and 8 is garbage.
+fn cell_value(content: BoardContent) -> u8 {
+    match content {
+        BoardContent::Empty => 0,
+        BoardContent::Tetromino(shape) => shape as u8 + 1,
+        BoardContent::Garbage | BoardContent::Blocked => 8,
+    }
+}
+
+/// The cells indexed by `[y, x]`.
+fn cell_array(board: &Board) -> Array2<u8> {
+    Array2::from_shape_fn((board.height(), board.width()), |(y, x)| {
+        cell_value(board.get(Position::new(x as i32, y as i32)))
+    })
+}
+
+/// The cells of the game's board with the falling piece drawn in.
+fn game_array(game: &Game) -> Array2<u8> {
+    let mut array = cell_array(game.board());
+    if *game.phase() == Phase::Falling && !game.is_game_over() {
+        let piece = game.current_tetromino();
+        for cell in piece.cells() {
+            if let Some(value) = array.get_mut((cell.y as usize, cell.x as usize)) {
+                *value = cell_value(BoardContent::Tetromino(piece.shape));
+            }
+        }
+    }
+    array
+}
+
+#[pyclass(name = "Tetromino", frozen)]
+#[derive(Clone)]
+pub struct PyTetromino(Tetromino);
+
+#[pymethods]
+impl PyTetromino {
+    #[getter]
+    fn shape(&self) -> Shape {
+        self.0.shape
+    }
+
+    #[getter]
+    fn x(&self) -> i32 {
+        self.0.position.x
+    }
+
+    #[getter]
+    fn y(&self) -> i32 {
+        self.0.position.y
+    }
+
+    #[getter]
+    fn orientation(&self) -> &'static str {
+        match self.0.orientation {
+            Orientation::North => "north",
+            Orientation::East => "east",
+            Orientation::South => "south",
+            Orientation::West => "west",
+        }
+    }
+
+    /// The `(x, y)` of every block.
+    fn cells(&self) -> Vec<(i32, i32)> {
+        self.0.cells().iter().map(|cell| (cell.x, cell.y)).collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Tetromino({:?}, x={}, y={}, {})",
+            self.0.shape,
+            self.0.position.x,
+            self.0.position.y,
+            self.orientation()
+        )
+    }
+}
+
+/// A copy of a game's board. Row 0 is the bottom row.
+#[pyclass(name = "Board", frozen)]
+pub struct PyBoard(Board);
+
+#[pymethods]
+impl PyBoard {
+    #[getter]
+    fn width(&self) -> usize {
+        self.0.width()
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.0.height()
+    }
+
+    fn is_free(&self, x: i32, y: i32) -> bool {
+        self.0.is_free(Position::new(x, y))
+    }
+
+    /// The cells as a `height` x `width` array of `uint8`, indexed by
+    /// `[y, x]`.
+    fn to_numpy<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<u8>> {
+        cell_array(&self.0).into_pyarray(py)
+    }
+
+    fn stack_height(&self) -> usize {
+        self.0.stack_height()
+    }
+
+    fn holes(&self) -> usize {
+        self.0.holes()
+    }
+
+    fn column_heights(&self) -> Vec<usize> {
+        self.0.column_heights()
+    }
+
+    fn bumpiness(&self) -> usize {
+        self.0.bumpiness()
+    }
+
+    fn spawn_clearance(&self) -> usize {
+        self.0.spawn_clearance()
+    }
+}
+
+/// `Game` is not thread safe, so it may only be used from the thread that
+/// created it.
+#[pyclass(name = "Game", unsendable)]
+pub struct PyGame(Game);
+
+#[pymethods]
+impl PyGame {
+    #[new]
+    #[pyo3(signature = (seed=None, ruleset="guideline"))]
+    fn new(seed: Option<u64>, ruleset: &str) -> PyResult<Self> {
+        let ruleset: Ruleset = ruleset.parse().map_err(PyValueError::new_err)?;
+        Ok(PyGame(match seed {
+            Some(seed) => Game::with_seed(ruleset, seed),
+            None => Game::with_ruleset(ruleset),
+        }))
+    }
+
+    /// Advances the game by one frame. Returns false once the game is over.
+    fn tick(&mut self) -> bool {
+        self.0.tick()
+    }
+
+    /// Applies a key press right away. Returns whether it did anything.
+    fn press(&mut self, key: Key) -> bool {
+        press(&mut self.0, key)
+    }
+
+    fn move_left(&mut self) -> bool {
+        self.0.move_left()
+    }
+
+    fn move_right(&mut self) -> bool {
+        self.0.move_right()
+    }
+
+    fn move_down(&mut self) -> bool {
+        self.0.move_down()
+    }
+
+    fn rotate_clockwise(&mut self) -> bool {
+        self.0.rotate_clockwise()
+    }
+
+    fn rotate_counterclockwise(&mut self) -> bool {
+        self.0.rotate_counterclockwise()
+    }
+
+    fn hold(&mut self) -> bool {
+        self.0.hold()
+    }
+
+    /// Returns the number of rows dropped, or `None` if no piece is falling.
+    fn hard_drop(&mut self) -> Option<usize> {
+        self.0.hard_drop()
+    }
+
+    #[getter]
+    fn board(&self) -> PyBoard {
+        PyBoard(self.0.board().clone())
+    }
+
+    /// The board with the falling piece drawn in, like `Board.to_numpy`.
+    fn board_array<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<u8>> {
+        game_array(&self.0).into_pyarray(py)
+    }
+
+    #[getter]
+    fn current_tetromino(&self) -> PyTetromino {
+        PyTetromino(self.0.current_tetromino())
+    }
+
+    #[getter]
+    fn held_shape(&self) -> Option<Shape> {
+        self.0.held_shape()
+    }
+
+    fn preview(&self, count: usize) -> Vec<Shape> {
+        self.0.preview(count)
+    }
+
+    /// "falling", "line_clear" or "entry".
+    #[getter]
+    fn phase(&self) -> &'static str {
+        match self.0.phase() {
+            Phase::Falling => "falling",
+            Phase::LineClear { .. } => "line_clear",
+            Phase::Entry { .. } => "entry",
+        }
+    }
+
+    #[getter]
+    fn score(&self) -> u32 {
+        self.0.score()
+    }
+
+    #[getter]
+    fn lines_removed(&self) -> usize {
+        self.0.lines_removed()
+    }
+
+    #[getter]
+    fn pieces_locked(&self) -> usize {
+        self.0.pieces_locked()
+    }
+
+    fn is_game_over(&self) -> bool {
+        self.0.is_game_over()
+    }
+}
+
+#[pymodule]
+fn tetris(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Shape>()?;
+    module.add_class::<Key>()?;
+    module.add_class::<PyTetromino>()?;
+    module.add_class::<PyBoard>()?;
+    module.add_class::<PyGame>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::empty_board;
+
+    #[test]
+    fn test_cell_value() {
+        assert_eq!(cell_value(BoardContent::Empty), 0);
+        assert_eq!(cell_value(BoardContent::Tetromino(Shape::I)), 1);
+        assert_eq!(cell_value(BoardContent::Tetromino(Shape::Z)), 7);
+        assert_eq!(cell_value(BoardContent::Garbage), 8);
+    }
+
+    #[test]
+    fn test_cell_array() {
+        let mut board = empty_board();
+        board.set(Position::new(2, 0), Shape::T);
+        board.set(Position::new(9, 5), Shape::O);
+        let array = cell_array(&board);
+        assert_eq!(array.dim(), (board.height(), board.width()));
+        // Indexed by [y, x] with row 0 at the bottom.
+        assert_eq!(array[[0, 2]], cell_value(BoardContent::Tetromino(Shape::T)));
+        assert_eq!(array[[5, 9]], cell_value(BoardContent::Tetromino(Shape::O)));
+        assert_eq!(array.iter().filter(|value| **value != 0).count(), 2);
+    }
+
+    #[test]
+    fn test_game_array() {
+        let mut game = Game::new();
+        game.spawn_specific(Shape::I);
+        let array = game_array(&game);
+        let piece = game.current_tetromino();
+        for cell in piece.cells() {
+            assert_eq!(array[[cell.y as usize, cell.x as usize]], 1);
+        }
+        assert_eq!(array.iter().filter(|value| **value != 0).count(), 4);
+        assert_eq!(cell_array(game.board()).iter().sum::<u8>(), 0);
+
+        game.hard_drop();
+        let array = game_array(&game);
+        assert_eq!(array.row(0).iter().filter(|value| **value == 1).count(), 4);
+        assert_eq!(*game.phase(), Phase::Falling);
+        assert_eq!(array.iter().filter(|value| **value != 0).count(), 8);
+
+        while !game.is_game_over() {
+            game.hard_drop();
+        }
+        assert_eq!(game_array(&game), cell_array(game.board()));
+    }
+}
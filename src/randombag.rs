@@ -1,16 +1,64 @@
 use crate::tetromino::{all_shapes, Shape};
 use rand::prelude::SliceRandom;
-use rand::rngs::ThreadRng;
-use rand::thread_rng;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// How the pieces are chosen.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum Randomizer {
+    /// Every piece once per seven, in random order.
+    #[default]
+    SevenBag,
+    /// Every piece independently, so droughts and floods happen.
+    Memoryless,
+}
+
+impl fmt::Display for Randomizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Randomizer::SevenBag => write!(f, "bag"),
+            Randomizer::Memoryless => write!(f, "memoryless"),
+        }
+    }
+}
+
+impl FromStr for Randomizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bag" => Ok(Randomizer::SevenBag),
+            "memoryless" => Ok(Randomizer::Memoryless),
+            _ => Err(format!("unknown randomizer: {}", s)),
+        }
+    }
+}
 
 pub struct RandomBag {
     contents: [Shape; 14],
     index: usize,
-    rng: ThreadRng,
+    randomizer: Randomizer,
+    rng: StdRng,
 }
 
 impl RandomBag {
     pub fn new() -> Self {
+        Self::with_rng(Randomizer::SevenBag, StdRng::from_entropy())
+    }
+
+    /// Creates a bag that always yields the same sequence for the same seed.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(Randomizer::SevenBag, StdRng::seed_from_u64(seed))
+    }
+
+    pub fn with_randomizer(randomizer: Randomizer, seed: u64) -> Self {
+        Self::with_rng(randomizer, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(randomizer: Randomizer, rng: StdRng) -> Self {
         let mut bag = RandomBag {
             contents: [
                 Shape::I,
@@ -29,17 +77,27 @@ impl RandomBag {
                 Shape::Z,
             ],
             index: 0,
-            rng: thread_rng(),
+            randomizer,
+            rng,
         };
         bag.shuffle_front();
         bag.shuffle_back();
         bag
     }
 
-    fn peek(&self) -> Shape {
+    pub fn peek(&self) -> Shape {
         self.contents[self.index]
     }
 
+    /// The next pieces in order, without taking them. At least seven are
+    /// always known, so fewer than `count` may be returned.
+    pub fn preview(&self, count: usize) -> Vec<Shape> {
+        let known = self.contents.len() - self.index % 7;
+        (0..count.min(known))
+            .map(|offset| self.contents[(self.index + offset) % self.contents.len()])
+            .collect()
+    }
+
     pub fn get(&mut self) -> Shape {
         let result = self.contents[self.index];
         self.index = (self.index + 1) % self.contents.len();
@@ -52,11 +110,29 @@ impl RandomBag {
     }
 
     fn shuffle_front(&mut self) {
-        self.contents[0..7].shuffle(&mut self.rng);
+        self.refill(0..7);
     }
 
     fn shuffle_back(&mut self) {
-        self.contents[7..14].shuffle(&mut self.rng);
+        self.refill(7..14);
+    }
+
+    fn refill(&mut self, range: Range<usize>) {
+        match self.randomizer {
+            Randomizer::SevenBag => self.contents[range].shuffle(&mut self.rng),
+            Randomizer::Memoryless => {
+                let shapes = all_shapes();
+                for index in range {
+                    self.contents[index] = *shapes.choose(&mut self.rng).unwrap();
+                }
+            }
+        }
+    }
+}
+
+impl Default for RandomBag {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -72,6 +148,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_preview() {
+        let mut bag = RandomBag::new();
+        for _ in 1..=140 {
+            let preview = bag.preview(20);
+            assert!(preview.len() >= 7);
+            assert!(preview.iter().all(|shape| *shape == bag.get()));
+        }
+    }
+
+    #[test]
+    fn test_memoryless() {
+        let mut bag = RandomBag::with_randomizer(Randomizer::Memoryless, 1);
+        let pieces: Vec<Shape> = (0..70).map(|_| bag.get()).collect();
+        let mut shapes = all_shapes();
+        shapes.sort();
+        assert!(pieces.chunks(7).any(|bag| {
+            let mut bag = bag.to_vec();
+            bag.sort();
+            bag != shapes
+        }));
+        assert_eq!("memoryless".parse(), Ok(Randomizer::Memoryless));
+        assert_eq!(Randomizer::SevenBag.to_string(), "bag");
+    }
+
+    #[test]
+    fn test_seed() {
+        let mut a = RandomBag::with_seed(42);
+        let mut b = RandomBag::with_seed(42);
+        for _ in 1..=140 {
+            assert_eq!(a.get(), b.get());
+        }
+    }
+
     #[test]
     fn test_count() {
         let bag = RandomBag::new();
@@ -0,0 +1,74 @@
+use std::str::FromStr;
+
+/// Rules that differ between Tetris variants.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Ruleset {
+    /// Whether the current piece can be swapped with a held one.
+    pub hold: bool,
+    /// Initial Rotation System: a rotation buffered while the next piece
+    /// spawns is applied to it immediately.
+    pub initial_rotation: bool,
+    /// Initial Hold System: a hold buffered while the next piece spawns
+    /// swaps it with the held piece immediately.
+    pub initial_hold: bool,
+    /// Entry delay (ARE): ticks between a piece locking and the next one
+    /// spawning.
+    pub entry_delay: u32,
+    /// Ticks full lines stay on the board before they are removed.
+    pub line_clear_delay: u32,
+}
+
+impl Ruleset {
+    /// Modern guideline games: no delays, IRS and IHS.
+    pub fn guideline() -> Self {
+        Ruleset {
+            hold: true,
+            initial_rotation: true,
+            initial_hold: true,
+            entry_delay: 0,
+            line_clear_delay: 0,
+        }
+    }
+
+    /// NES Tetris: no hold and no initial actions.
+    pub fn nes() -> Self {
+        Ruleset {
+            hold: false,
+            initial_rotation: false,
+            initial_hold: false,
+            entry_delay: 10,
+            line_clear_delay: 18,
+        }
+    }
+
+    /// The Tetris: The Grand Master timings with IRS and no hold.
+    pub fn tgm() -> Self {
+        Ruleset {
+            hold: false,
+            initial_rotation: true,
+            initial_hold: false,
+            entry_delay: 30,
+            line_clear_delay: 41,
+        }
+    }
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Self::guideline()
+    }
+}
+
+impl FromStr for Ruleset {
+    type Err = String;
+
+    /// Parses the name of a preset.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "guideline" => Ok(Ruleset::guideline()),
+            "nes" => Ok(Ruleset::nes()),
+            "tgm" => Ok(Ruleset::tgm()),
+            _ => Err(format!("unknown ruleset: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
+}
+
+/// What a single lock cleared.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Clear {
+    pub lines: usize,
+    pub tspin: TSpin,
+    /// Whether the clear left the board empty.
+    pub perfect_clear: bool,
+}
+
+impl Clear {
+    /// Tetrises and T-spins that clear lines continue a back-to-back chain.
+    pub fn is_difficult(&self) -> bool {
+        self.lines > 0 && (self.lines >= 4 || self.tspin != TSpin::None)
+    }
+}
+
+/// How a single lock was scored.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LockScore {
+    pub clear: Clear,
+    pub points: u32,
+    /// The combo counter after this lock, see `Scoring::combo`.
+    pub combo: Option<u32>,
+    /// Whether this lock got the back-to-back bonus.
+    pub back_to_back: bool,
+}
+
+/// Points for a clear at level 1, before back-to-back and combo bonuses.
+pub fn clear_points(clear: Clear) -> u32 {
+    match (clear.tspin, clear.lines) {
+        (TSpin::None, 0) => 0,
+        (TSpin::None, 1) => 100,
+        (TSpin::None, 2) => 300,
+        (TSpin::None, 3) => 500,
+        (TSpin::None, _) => 800,
+        (TSpin::Mini, 0) => 100,
+        (TSpin::Mini, 1) => 200,
+        (TSpin::Mini, _) => 400,
+        (TSpin::Full, 0) => 400,
+        (TSpin::Full, 1) => 800,
+        (TSpin::Full, 2) => 1200,
+        (TSpin::Full, _) => 1600,
+    }
+}
+
+/// Guideline scoring: line clears and T-spins scaled by level, a 1.5x
+/// back-to-back bonus, combo bonuses and points for soft and hard drops.
+#[derive(Debug, Clone)]
+pub struct Scoring {
+    score: u32,
+    combo: Option<u32>,
+    back_to_back: bool,
+}
+
+impl Scoring {
+    pub fn new() -> Self {
+        Scoring {
+            score: 0,
+            combo: None,
+            back_to_back: false,
+        }
+    }
+
+    /// Scores a lock.
+    pub fn lock(&mut self, clear: Clear, level: u32) -> LockScore {
+        let mut points = clear_points(clear) * level;
+        let back_to_back = clear.is_difficult() && self.back_to_back;
+        if clear.lines > 0 {
+            if back_to_back {
+                points = points * 3 / 2;
+            }
+            self.back_to_back = clear.is_difficult();
+            let combo = self.combo.map_or(0, |combo| combo + 1);
+            points += 50 * combo * level;
+            self.combo = Some(combo);
+        } else {
+            self.combo = None;
+        }
+        self.score += points;
+        LockScore {
+            clear,
+            points,
+            combo: self.combo,
+            back_to_back,
+        }
+    }
+
+    pub fn soft_drop(&mut self, rows: usize) {
+        self.score += rows as u32;
+    }
+
+    pub fn hard_drop(&mut self, rows: usize) {
+        self.score += 2 * rows as u32;
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// Number of consecutive clearing locks minus one, or `None` if the last
+    /// lock did not clear anything.
+    pub fn combo(&self) -> Option<u32> {
+        self.combo
+    }
+
+    /// Whether the last clear was difficult, so the next difficult clear gets
+    /// the back-to-back bonus.
+    pub fn back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(lines: usize, tspin: TSpin) -> Clear {
+        Clear {
+            lines,
+            tspin,
+            perfect_clear: false,
+        }
+    }
+
+    #[test]
+    fn test_level_multiplier() {
+        let mut scoring = Scoring::new();
+        assert_eq!(scoring.lock(clear(1, TSpin::None), 3).points, 300);
+        assert_eq!(scoring.lock(clear(0, TSpin::None), 3).points, 0);
+        assert_eq!(scoring.lock(clear(2, TSpin::Full), 2).points, 2400);
+        assert_eq!(scoring.score(), 2700);
+    }
+
+    #[test]
+    fn test_back_to_back() {
+        let mut scoring = Scoring::new();
+        assert_eq!(scoring.lock(clear(4, TSpin::None), 1).points, 800);
+        assert!(scoring.back_to_back());
+        assert_eq!(scoring.lock(clear(0, TSpin::None), 1).points, 0);
+        assert_eq!(scoring.lock(clear(1, TSpin::Full), 1).points, 1200);
+        assert_eq!(scoring.lock(clear(0, TSpin::None), 1).points, 0);
+        assert_eq!(scoring.lock(clear(1, TSpin::None), 1).points, 100);
+        assert!(!scoring.back_to_back());
+        assert_eq!(scoring.lock(clear(0, TSpin::None), 1).points, 0);
+        assert_eq!(scoring.lock(clear(4, TSpin::None), 1).points, 800);
+    }
+
+    #[test]
+    fn test_combo() {
+        let mut scoring = Scoring::new();
+        assert_eq!(scoring.lock(clear(1, TSpin::None), 1).points, 100);
+        assert_eq!(scoring.combo(), Some(0));
+        assert_eq!(scoring.lock(clear(1, TSpin::None), 1).points, 150);
+        assert_eq!(scoring.lock(clear(2, TSpin::None), 2).points, 800);
+        assert_eq!(scoring.combo(), Some(2));
+        assert_eq!(scoring.lock(clear(0, TSpin::None), 1).points, 0);
+        assert_eq!(scoring.combo(), None);
+    }
+
+    #[test]
+    fn test_drops() {
+        let mut scoring = Scoring::new();
+        scoring.soft_drop(3);
+        scoring.hard_drop(10);
+        assert_eq!(scoring.score(), 23);
+    }
+}
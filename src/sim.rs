@@ -0,0 +1,337 @@
+use crate::bot::{Bot, BotSettings};
+use crate::game::{Game, Phase, TopOut};
+use crate::randombag::{RandomBag, Randomizer};
+use crate::ruleset::Ruleset;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// How pieces rotate and kick. SRS is the only rotation system the game
+/// has, so it is the only value.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum RotationSystem {
+    #[default]
+    Srs,
+}
+
+impl fmt::Display for RotationSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RotationSystem::Srs => write!(f, "srs"),
+        }
+    }
+}
+
+impl FromStr for RotationSystem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "srs" => Ok(RotationSystem::Srs),
+            _ => Err(format!("unknown rotation system: {}", s)),
+        }
+    }
+}
+
+/// What a batch of headless bot games is played with.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SimConfig {
+    pub ruleset: Ruleset,
+    pub randomizer: Randomizer,
+    pub rotation: RotationSystem,
+    pub bot: BotSettings,
+    /// One game is played per seed.
+    pub seeds: Range<u64>,
+    /// Games end after this many pieces even if not lost.
+    pub max_pieces: usize,
+    pub threads: usize,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            ruleset: Ruleset::default(),
+            randomizer: Randomizer::default(),
+            rotation: RotationSystem::default(),
+            bot: BotSettings::default(),
+            seeds: 0..100,
+            max_pieces: 1000,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+}
+
+/// Why a simulated game ended.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndReason {
+    BlockOut,
+    LockOut,
+    GarbageTopOut,
+    PieceLimit,
+    /// The bot found no move that does not top out.
+    NoMove,
+}
+
+const END_REASONS: [EndReason; 5] = [
+    EndReason::BlockOut,
+    EndReason::LockOut,
+    EndReason::GarbageTopOut,
+    EndReason::PieceLimit,
+    EndReason::NoMove,
+];
+
+impl From<TopOut> for EndReason {
+    fn from(top_out: TopOut) -> Self {
+        match top_out {
+            TopOut::BlockOut => EndReason::BlockOut,
+            TopOut::LockOut => EndReason::LockOut,
+            TopOut::Garbage => EndReason::GarbageTopOut,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct GameResult {
+    pub seed: u64,
+    pub pieces: usize,
+    pub lines: usize,
+    pub score: u32,
+    pub attack: u32,
+    pub reason: EndReason,
+}
+
+/// Plays the game of a single seed.
+pub fn play_game(config: &SimConfig, seed: u64) -> GameResult {
+    let bag = RandomBag::with_randomizer(config.randomizer, seed);
+    let mut game = Game::with_bag(config.ruleset, bag);
+    let mut bot = Bot::new(config.bot.clone());
+    let reason = loop {
+        if let Some(top_out) = game.top_out_reason() {
+            break top_out.into();
+        }
+        if game.pieces_locked() >= config.max_pieces {
+            break EndReason::PieceLimit;
+        }
+        if *game.phase() == Phase::Falling && bot.play(&mut game).is_none() {
+            break EndReason::NoMove;
+        }
+        game.tick();
+    };
+    GameResult {
+        seed,
+        pieces: game.pieces_locked(),
+        lines: game.lines_removed(),
+        score: game.score(),
+        attack: game.attack_sent(),
+        reason,
+    }
+}
+
+/// Plays every seed on `config.threads` threads. Results are sorted by seed.
+pub fn simulate(config: &SimConfig) -> Vec<GameResult> {
+    let next_seed = AtomicU64::new(config.seeds.start);
+    let results = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..config.threads.max(1) {
+            scope.spawn(|| loop {
+                let seed = next_seed.fetch_add(1, Ordering::Relaxed);
+                if seed >= config.seeds.end {
+                    break;
+                }
+                let result = play_game(config, seed);
+                results.lock().unwrap().push(result);
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|result| result.seed);
+    results
+}
+
+/// Aggregate statistics over many games.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Summary {
+    pub games: usize,
+    pub mean_pieces: f64,
+    pub mean_lines: f64,
+    pub median_lines: f64,
+    pub min_lines: usize,
+    pub max_lines: usize,
+    pub mean_score: f64,
+    /// Scores by percentile, 0 being the lowest and 100 the highest score.
+    pub score_percentiles: BTreeMap<usize, u32>,
+    pub mean_attack: f64,
+    pub end_reasons: BTreeMap<EndReason, usize>,
+}
+
+const PERCENTILES: [usize; 7] = [0, 10, 25, 50, 75, 90, 100];
+
+impl Summary {
+    pub fn new(results: &[GameResult]) -> Self {
+        let games = results.len();
+        let mean = |value: fn(&GameResult) -> f64| {
+            if games == 0 {
+                0.0
+            } else {
+                results.iter().map(value).sum::<f64>() / games as f64
+            }
+        };
+        let mut lines: Vec<usize> = results.iter().map(|result| result.lines).collect();
+        lines.sort();
+        let median_lines = match games {
+            0 => 0.0,
+            _ if games.is_multiple_of(2) => (lines[games / 2 - 1] + lines[games / 2]) as f64 / 2.0,
+            _ => lines[games / 2] as f64,
+        };
+        let mut scores: Vec<u32> = results.iter().map(|result| result.score).collect();
+        scores.sort();
+        let score_percentiles = PERCENTILES
+            .iter()
+            .filter(|_| games > 0)
+            .map(|p| (*p, scores[(games - 1) * p / 100]))
+            .collect();
+        let mut end_reasons = BTreeMap::new();
+        for result in results {
+            *end_reasons.entry(result.reason).or_default() += 1;
+        }
+        Summary {
+            games,
+            mean_pieces: mean(|result| result.pieces as f64),
+            mean_lines: mean(|result| result.lines as f64),
+            median_lines,
+            min_lines: lines.first().copied().unwrap_or(0),
+            max_lines: lines.last().copied().unwrap_or(0),
+            mean_score: mean(|result| result.score as f64),
+            score_percentiles,
+            mean_attack: mean(|result| result.attack as f64),
+            end_reasons,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("summaries serialize to JSON")
+    }
+
+    /// A header line and a line of values. Every percentile and end reason
+    /// gets its own column.
+    pub fn to_csv(&self) -> String {
+        let mut columns = vec![
+            ("games".to_string(), self.games.to_string()),
+            (
+                "mean_pieces".to_string(),
+                format!("{:.2}", self.mean_pieces),
+            ),
+            ("mean_lines".to_string(), format!("{:.2}", self.mean_lines)),
+            ("median_lines".to_string(), self.median_lines.to_string()),
+            ("min_lines".to_string(), self.min_lines.to_string()),
+            ("max_lines".to_string(), self.max_lines.to_string()),
+            ("mean_score".to_string(), format!("{:.2}", self.mean_score)),
+        ];
+        for p in PERCENTILES {
+            let score = self.score_percentiles.get(&p).copied().unwrap_or(0);
+            columns.push((format!("score_p{}", p), score.to_string()));
+        }
+        columns.push((
+            "mean_attack".to_string(),
+            format!("{:.2}", self.mean_attack),
+        ));
+        for reason in END_REASONS {
+            let name = serde_json::to_value(reason).expect("reasons serialize to JSON");
+            let count = self.end_reasons.get(&reason).copied().unwrap_or(0);
+            columns.push((name.as_str().unwrap().to_string(), count.to_string()));
+        }
+        let (header, values): (Vec<String>, Vec<String>) = columns.into_iter().unzip();
+        format!("{}\n{}\n", header.join(","), values.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(seed: u64, lines: usize, score: u32, reason: EndReason) -> GameResult {
+        GameResult {
+            seed,
+            pieces: lines * 3,
+            lines,
+            score,
+            attack: 0,
+            reason,
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let results = [
+            result(0, 10, 100, EndReason::BlockOut),
+            result(1, 30, 400, EndReason::PieceLimit),
+            result(2, 20, 300, EndReason::PieceLimit),
+            result(3, 40, 200, EndReason::PieceLimit),
+        ];
+        let summary = Summary::new(&results);
+        assert_eq!(summary.mean_lines, 25.0);
+        assert_eq!(summary.median_lines, 25.0);
+        assert_eq!(summary.min_lines, 10);
+        assert_eq!(summary.score_percentiles[&0], 100);
+        assert_eq!(summary.score_percentiles[&50], 200);
+        assert_eq!(summary.score_percentiles[&100], 400);
+        assert_eq!(summary.end_reasons[&EndReason::PieceLimit], 3);
+        assert_eq!(Summary::new(&[]).games, 0);
+    }
+
+    #[test]
+    fn test_csv() {
+        let summary = Summary::new(&[result(0, 4, 800, EndReason::LockOut)]);
+        let csv = summary.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let header: Vec<&str> = lines[0].split(',').collect();
+        let values: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(header.len(), values.len());
+        let column = |name: &str| values[header.iter().position(|h| *h == name).unwrap()];
+        assert_eq!(column("score_p90"), "800");
+        assert_eq!(column("lock_out"), "1");
+        assert_eq!(column("block_out"), "0");
+        assert_eq!(column("garbage_top_out"), "0");
+        assert_eq!(column("no_move"), "0");
+    }
+
+    #[test]
+    fn test_simulate() {
+        let config = SimConfig {
+            bot: BotSettings {
+                width: 1,
+                depth: 1,
+                previews: 0,
+                ..BotSettings::default()
+            },
+            seeds: 5..8,
+            max_pieces: 10,
+            threads: 2,
+            ..SimConfig::default()
+        };
+        let results = simulate(&config);
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| result.seed)
+                .collect::<Vec<u64>>(),
+            vec![5, 6, 7]
+        );
+        assert!(results.iter().all(|result| result.pieces == 10));
+        assert_eq!(results[1], play_game(&config, 6));
+    }
+
+    #[test]
+    fn test_rotation_system() {
+        assert_eq!("srs".parse(), Ok(RotationSystem::Srs));
+        assert_eq!(RotationSystem::Srs.to_string(), "srs");
+        assert!("ars".parse::<RotationSystem>().is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::event::GameEvent;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+/// A line of the spectator stream: an event and the frame it happened in.
+#[derive(Serialize)]
+struct StreamLine<'a> {
+    frame: u64,
+    #[serde(flatten)]
+    event: &'a GameEvent,
+}
+
+/// Broadcasts game events as newline-delimited JSON to everyone connected,
+/// for overlays, commentary tools and recorders. Each line looks like
+/// `{"frame":12,"type":"moved","piece":{...}}`.
+///
+/// New connections are picked up on the next `publish` without waiting.
+/// Writing never blocks the game: output a spectator can't take yet is
+/// buffered, and spectators whose connection fails or whose buffer grows
+/// past the limit are dropped.
+pub struct SpectatorServer {
+    listener: TcpListener,
+    spectators: Vec<Spectator>,
+    buffer_limit: usize,
+}
+
+/// Bytes buffered for a spectator before it is dropped, by default.
+pub const DEFAULT_BUFFER_LIMIT: usize = 1 << 20;
+
+struct Spectator {
+    stream: TcpStream,
+    /// Output not written yet.
+    pending: Vec<u8>,
+}
+
+impl Spectator {
+    /// Writes as much of the pending output as the connection takes without
+    /// blocking. Returns false if the connection failed.
+    fn flush(&mut self) -> bool {
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return false,
+                Ok(written) => {
+                    self.pending.drain(..written);
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+}
+
+impl SpectatorServer {
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(SpectatorServer {
+            listener,
+            spectators: Vec::new(),
+            buffer_limit: DEFAULT_BUFFER_LIMIT,
+        })
+    }
+
+    /// Sets how many bytes may be waiting for a slow spectator before it is
+    /// dropped.
+    pub fn set_buffer_limit(&mut self, bytes: usize) {
+        self.buffer_limit = bytes;
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn spectators(&self) -> usize {
+        self.spectators.len()
+    }
+
+    /// Accepts new spectators and sends them the events of `frame`, along
+    /// with anything still buffered.
+    pub fn publish(&mut self, frame: u64, events: &[GameEvent]) {
+        self.accept();
+        let mut lines = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut lines, &StreamLine { frame, event })
+                .expect("events serialize to JSON");
+            lines.push(b'\n');
+        }
+        let limit = self.buffer_limit;
+        self.spectators.retain_mut(|spectator| {
+            spectator.pending.extend_from_slice(&lines);
+            spectator.flush() && spectator.pending.len() <= limit
+        });
+    }
+
+    fn accept(&mut self) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.spectators.push(Spectator {
+                    stream,
+                    pending: Vec::new(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use serde_json::Value;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn test_spectator_receives_events() {
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        let mut game = Game::new();
+        game.record_events();
+        game.move_left();
+        game.hard_drop();
+        server.publish(3, &game.take_events());
+        assert_eq!(server.spectators(), 1);
+
+        let mut lines = BufReader::new(stream).lines();
+        let line: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(line["frame"], 3);
+        assert_eq!(line["type"], "moved");
+        assert_eq!(line["piece"]["position"]["x"], 3);
+        let types: Vec<Value> = lines
+            .take(3)
+            .map(|line| serde_json::from_str::<Value>(&line.unwrap()).unwrap()["type"].clone())
+            .collect();
+        assert_eq!(types, ["moved", "locked", "spawned"]);
+    }
+
+    #[test]
+    fn test_slow_spectator_is_dropped() {
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        server.set_buffer_limit(1024);
+        let _stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        let mut game = Game::new();
+        game.record_events();
+        game.hard_drop();
+        let dropped = game.take_events();
+        let events: Vec<GameEvent> = (0..10_000).flat_map(|_| dropped.clone()).collect();
+        server.publish(0, &[]);
+        assert_eq!(server.spectators(), 1);
+        let mut frame = 0;
+        while server.spectators() > 0 && frame < 100 {
+            server.publish(frame, &events);
+            frame += 1;
+        }
+        assert_eq!(server.spectators(), 0);
+    }
+
+    #[test]
+    fn test_events_roundtrip() {
+        let mut game = Game::new();
+        game.record_events();
+        game.rotate_clockwise();
+        game.hard_drop();
+        for event in game.take_events() {
+            let json = serde_json::to_string(&event).unwrap();
+            assert_eq!(serde_json::from_str::<GameEvent>(&json).unwrap(), event);
+        }
+    }
+}
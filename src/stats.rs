@@ -0,0 +1,360 @@
+use crate::event::GameEvent;
+use crate::game::Game;
+use crate::mode::ticks_to_seconds;
+use crate::scoring::{Clear, LockScore, Scoring, TSpin};
+use crate::tetromino::Shape;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver};
+
+/// A stack at least this high counts as being in danger.
+pub const DANGER_HEIGHT: usize = 16;
+
+/// Collects statistics over a game. Locks and clears are counted from the
+/// game's events, so none are missed however many happen between updates.
+/// Call `update` once per frame after the game ticked to sample the stack.
+#[derive(Debug)]
+pub struct StatsCollector {
+    events: Receiver<GameEvent>,
+    /// Replays the clears to follow combos and back-to-backs.
+    scoring: Scoring,
+    /// The clear of the last lock, until its `LinesCleared` may have come.
+    pending: Option<Clear>,
+    ticks: u64,
+    pieces: usize,
+    pieces_by_shape: BTreeMap<Shape, usize>,
+    clears: BTreeMap<String, usize>,
+    tspins: usize,
+    perfect_clears: usize,
+    max_combo: u32,
+    back_to_backs: usize,
+    back_to_back_streak: usize,
+    max_back_to_back: usize,
+    finesse_faults: usize,
+    holes: usize,
+    holes_created: usize,
+    height_sum: u64,
+    danger_ticks: u64,
+    key_presses: usize,
+    lines: usize,
+    attack: u32,
+    score: u32,
+}
+
+impl StatsCollector {
+    /// Starts collecting statistics of `game` by listening to its events.
+    pub fn new(game: &mut Game) -> Self {
+        let (sender, events) = mpsc::channel();
+        game.add_listener(sender);
+        StatsCollector {
+            events,
+            scoring: Scoring::new(),
+            pending: None,
+            ticks: 0,
+            pieces: 0,
+            pieces_by_shape: BTreeMap::new(),
+            clears: BTreeMap::new(),
+            tspins: 0,
+            perfect_clears: 0,
+            max_combo: 0,
+            back_to_backs: 0,
+            back_to_back_streak: 0,
+            max_back_to_back: 0,
+            finesse_faults: 0,
+            holes: 0,
+            holes_created: 0,
+            height_sum: 0,
+            danger_ticks: 0,
+            key_presses: 0,
+            lines: 0,
+            attack: 0,
+            score: 0,
+        }
+    }
+
+    pub fn update(&mut self, game: &Game) {
+        self.ticks += 1;
+        let pieces = self.pieces;
+        while let Ok(event) = self.events.try_recv() {
+            self.record_event(event);
+        }
+        self.finish_lock();
+        let height = game.board().stack_height();
+        self.height_sum += height as u64;
+        if height >= DANGER_HEIGHT {
+            self.danger_ticks += 1;
+        }
+        let holes = game.board().holes();
+        if self.pieces > pieces {
+            self.holes_created += holes.saturating_sub(self.holes);
+        }
+        self.holes = holes;
+        self.key_presses = game.key_presses();
+        self.lines = game.lines_removed();
+        self.attack = game.attack_sent();
+        self.score = game.score();
+    }
+
+    fn record_event(&mut self, event: GameEvent) {
+        match event {
+            GameEvent::Locked { piece, tspin } => {
+                self.finish_lock();
+                self.pieces += 1;
+                *self.pieces_by_shape.entry(piece.shape).or_default() += 1;
+                self.pending = Some(Clear {
+                    lines: 0,
+                    tspin,
+                    perfect_clear: false,
+                });
+            }
+            GameEvent::LinesCleared {
+                rows,
+                tspin,
+                perfect_clear,
+            } => {
+                self.pending = Some(Clear {
+                    lines: rows.len(),
+                    tspin,
+                    perfect_clear,
+                });
+            }
+            _ => (),
+        }
+    }
+
+    /// `LinesCleared` directly follows its `Locked`, so a lock is complete
+    /// once the next one comes or the events run out.
+    fn finish_lock(&mut self) {
+        if let Some(clear) = self.pending.take() {
+            let lock = self.scoring.lock(clear, 1);
+            self.record_lock(lock);
+        }
+    }
+
+    fn record_lock(&mut self, lock: LockScore) {
+        let clear = lock.clear;
+        if let Some(name) = clear_name(clear) {
+            *self.clears.entry(name).or_default() += 1;
+        }
+        if clear.tspin != TSpin::None {
+            self.tspins += 1;
+        }
+        if clear.perfect_clear {
+            self.perfect_clears += 1;
+        }
+        self.max_combo = self.max_combo.max(lock.combo.unwrap_or(0));
+        if lock.back_to_back {
+            self.back_to_backs += 1;
+            self.back_to_back_streak += 1;
+            self.max_back_to_back = self.max_back_to_back.max(self.back_to_back_streak);
+        } else if clear.lines > 0 {
+            self.back_to_back_streak = 0;
+        }
+    }
+
+    /// Adds finesse faults, as found by a `FinesseAnalyser`.
+    pub fn add_finesse_faults(&mut self, faults: usize) {
+        self.finesse_faults += faults;
+    }
+
+    pub fn report(&self) -> StatsReport {
+        let seconds = ticks_to_seconds(self.ticks);
+        let per_second = |value: f64| if seconds > 0.0 { value / seconds } else { 0.0 };
+        StatsReport {
+            ticks: self.ticks,
+            seconds,
+            pieces: self.pieces,
+            pieces_by_shape: self.pieces_by_shape.clone(),
+            lines: self.lines,
+            score: self.score,
+            clears: self.clears.clone(),
+            tspins: self.tspins,
+            perfect_clears: self.perfect_clears,
+            max_combo: self.max_combo,
+            back_to_backs: self.back_to_backs,
+            max_back_to_back: self.max_back_to_back,
+            pieces_per_second: per_second(self.pieces as f64),
+            keys_per_piece: if self.pieces > 0 {
+                self.key_presses as f64 / self.pieces as f64
+            } else {
+                0.0
+            },
+            attack_per_minute: per_second(self.attack as f64) * 60.0,
+            finesse_faults: self.finesse_faults,
+            holes_created: self.holes_created,
+            average_stack_height: if self.ticks > 0 {
+                self.height_sum as f64 / self.ticks as f64
+            } else {
+                0.0
+            },
+            seconds_in_danger: ticks_to_seconds(self.danger_ticks),
+        }
+    }
+}
+
+/// Names clears like `"tetris"`, `"tspin_double"` or `"tspin_mini_zero"`.
+/// Locks that neither cleared lines nor were T-spins have no name.
+pub fn clear_name(clear: Clear) -> Option<String> {
+    let lines = match clear.lines {
+        0 => "zero",
+        1 => "single",
+        2 => "double",
+        3 => "triple",
+        _ => "tetris",
+    };
+    match clear.tspin {
+        TSpin::None if clear.lines == 0 => None,
+        TSpin::None => Some(lines.to_string()),
+        TSpin::Mini => Some(format!("tspin_mini_{}", lines)),
+        TSpin::Full => Some(format!("tspin_{}", lines)),
+    }
+}
+
+/// Statistics of a finished game.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct StatsReport {
+    pub ticks: u64,
+    pub seconds: f64,
+    pub pieces: usize,
+    pub pieces_by_shape: BTreeMap<Shape, usize>,
+    pub lines: usize,
+    pub score: u32,
+    /// How often each kind of clear happened, see `clear_name`.
+    pub clears: BTreeMap<String, usize>,
+    pub tspins: usize,
+    pub perfect_clears: usize,
+    pub max_combo: u32,
+    /// Clears that got the back-to-back bonus.
+    pub back_to_backs: usize,
+    /// The longest run of consecutive back-to-back bonuses.
+    pub max_back_to_back: usize,
+    pub pieces_per_second: f64,
+    pub keys_per_piece: f64,
+    pub attack_per_minute: f64,
+    pub finesse_faults: usize,
+    pub holes_created: usize,
+    pub average_stack_height: f64,
+    pub seconds_in_danger: f64,
+}
+
+impl StatsReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("reports serialize to JSON")
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Time            {:.2}s", self.seconds)?;
+        writeln!(f, "Score           {}", self.score)?;
+        writeln!(f, "Lines           {}", self.lines)?;
+        write!(f, "Pieces          {}", self.pieces)?;
+        for (shape, count) in self.pieces_by_shape.iter() {
+            write!(f, "  {:?}:{}", shape, count)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "PPS             {:.2}", self.pieces_per_second)?;
+        writeln!(f, "KPP             {:.2}", self.keys_per_piece)?;
+        writeln!(f, "APM             {:.1}", self.attack_per_minute)?;
+        writeln!(f, "Clears")?;
+        for (name, count) in self.clears.iter() {
+            writeln!(f, "  {:<14}{}", name, count)?;
+        }
+        writeln!(f, "T-spins         {}", self.tspins)?;
+        writeln!(f, "Perfect clears  {}", self.perfect_clears)?;
+        writeln!(f, "Max combo       {}", self.max_combo)?;
+        writeln!(
+            f,
+            "Back-to-back    {} (longest chain {})",
+            self.back_to_backs, self.max_back_to_back
+        )?;
+        writeln!(f, "Finesse faults  {}", self.finesse_faults)?;
+        writeln!(f, "Holes created   {}", self.holes_created)?;
+        writeln!(f, "Average height  {:.1}", self.average_stack_height)?;
+        writeln!(f, "Time in danger  {:.2}s", self.seconds_in_danger)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::tests::clear_four_lines;
+
+    #[test]
+    fn test_clear_name() {
+        let clear = |lines, tspin| Clear {
+            lines,
+            tspin,
+            perfect_clear: false,
+        };
+        assert_eq!(clear_name(clear(0, TSpin::None)), None);
+        assert_eq!(clear_name(clear(4, TSpin::None)).unwrap(), "tetris");
+        assert_eq!(clear_name(clear(2, TSpin::Full)).unwrap(), "tspin_double");
+        assert_eq!(
+            clear_name(clear(0, TSpin::Mini)).unwrap(),
+            "tspin_mini_zero"
+        );
+    }
+
+    #[test]
+    fn test_collect() {
+        let mut game = Game::new();
+        let mut stats = StatsCollector::new(&mut game);
+        clear_four_lines(&mut game);
+        clear_four_lines(&mut game);
+        stats.update(&game);
+        let report = stats.report();
+        assert_eq!(report.ticks, 1);
+        assert_eq!(report.pieces, game.pieces_locked());
+        assert_eq!(
+            report.pieces_by_shape.values().sum::<usize>(),
+            report.pieces
+        );
+        assert_eq!(report.lines, 8);
+        assert_eq!(report.clears["tetris"], 2);
+        assert_eq!(report.back_to_backs, 1);
+        assert_eq!(report.max_back_to_back, 1);
+        assert_eq!(report.max_combo, 0);
+        assert_eq!(report.pieces_by_shape[&Shape::I], 20);
+        assert!(report.attack_per_minute > 0.0);
+        assert_eq!(report.seconds_in_danger, 0.0);
+    }
+
+    #[test]
+    fn test_holes_and_danger() {
+        let mut game = Game::new();
+        let mut stats = StatsCollector::new(&mut game);
+        while !game.is_game_over() {
+            game.hard_drop();
+            stats.update(&game);
+        }
+        let report = stats.report();
+        assert!(report.seconds_in_danger > 0.0);
+        assert!(report.average_stack_height > 0.0);
+        assert_eq!(report.pieces, game.pieces_locked());
+        assert_eq!(
+            report.pieces_by_shape.values().sum::<usize>(),
+            report.pieces
+        );
+    }
+
+    #[test]
+    fn test_report_formats() {
+        let mut game = Game::new();
+        let mut stats = StatsCollector::new(&mut game);
+        clear_four_lines(&mut game);
+        stats.update(&game);
+        let report = stats.report();
+        assert_eq!(StatsReport::from_json(&report.to_json()).unwrap(), report);
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["clears"]["tetris"], 1);
+        let text = report.to_string();
+        assert!(text.contains("tetris"));
+        assert!(text.contains("Lines           4"));
+    }
+}
@@ -1,6 +1,8 @@
 use crate::position::Position;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "python", pyo3::pyclass(eq, eq_int, hash, frozen))]
 pub enum Shape {
     I,
     O,
@@ -23,14 +25,14 @@ pub fn all_shapes() -> Vec<Shape> {
     ]
 }
 
-#[derive(Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub struct Tetromino {
     pub position: Position,
     pub shape: Shape,
     pub orientation: Orientation,
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum Orientation {
     North,
     East,
@@ -56,6 +58,9 @@ fn rotate_counterclockwise(orientation: Orientation) -> Orientation {
     }
 }
 
+/// Where every tetromino spawns, above the visible part of the board.
+pub(crate) const SPAWN_POSITION: Position = Position::new(4, 22);
+
 impl Tetromino {
     pub fn new(position: Position, shape: Shape) -> Self {
         Self {
@@ -65,6 +70,11 @@ impl Tetromino {
         }
     }
 
+    /// A newly spawned tetromino of `shape`.
+    pub(crate) fn spawn(shape: Shape) -> Self {
+        Self::new(SPAWN_POSITION, shape)
+    }
+
     pub fn get_rotated_clockwise(&self) -> Tetromino {
         Tetromino {
             orientation: rotate_clockwise(self.orientation),
@@ -105,6 +115,11 @@ impl Tetromino {
     pub fn blocks(&self) -> [Position; 4] {
         tetromino_blocks(self.shape, self.orientation)
     }
+
+    /// The positions of the blocks on the board.
+    pub fn cells(&self) -> [Position; 4] {
+        self.blocks().map(|block| block + self.position)
+    }
 }
 
 fn tetromino_blocks(shape: Shape, orientation: Orientation) -> [Position; 4] {
@@ -155,10 +170,12 @@ fn tetromino_blocks(shape: Shape, orientation: Orientation) -> [Position; 4] {
     .map(|[x, y]| Position::new(x, y))
 }
 
+#[cfg(test)]
 fn rotate_position_clockwise(position: Position) -> Position {
     Position::new(position.y, -position.x)
 }
 
+#[cfg(test)]
 fn rotate_positions_clockwise(coordinates: [Position; 4]) -> [Position; 4] {
     coordinates.map(rotate_position_clockwise)
 }
@@ -0,0 +1,403 @@
+use crate::bot::{Bot, BotRunner, BotSettings, Weights};
+use crate::game::Game;
+use crate::ruleset::Ruleset;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+
+/// What a weight vector is judged by, averaged over the games it plays.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fitness {
+    Lines,
+    Score,
+}
+
+impl fmt::Display for Fitness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fitness::Lines => write!(f, "lines"),
+            Fitness::Score => write!(f, "score"),
+        }
+    }
+}
+
+impl FromStr for Fitness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lines" => Ok(Fitness::Lines),
+            "score" => Ok(Fitness::Score),
+            _ => Err(format!("unknown fitness: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct TunerSettings {
+    pub population: usize,
+    /// The best this many weight vectors survive unchanged.
+    pub elite: usize,
+    pub generations: usize,
+    /// Games played by every weight vector per generation.
+    pub games: usize,
+    /// Games end after this many pieces even if not lost.
+    pub max_pieces: usize,
+    /// How far mutations move a weight, relative to its size.
+    pub mutation: f64,
+    pub fitness: Fitness,
+    pub threads: usize,
+    /// Determines the games played and the random choices of the tuner.
+    pub seed: u64,
+}
+
+impl Default for TunerSettings {
+    fn default() -> Self {
+        TunerSettings {
+            population: 24,
+            elite: 4,
+            generations: 20,
+            games: 4,
+            max_pieces: 500,
+            mutation: 0.3,
+            fitness: Fitness::Lines,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Generation {
+    pub generation: usize,
+    pub best_fitness: f64,
+    pub mean_fitness: f64,
+    pub best: Weights,
+}
+
+/// Everything a tuning run found, as written to the results file.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct TuningResults {
+    pub settings: TunerSettings,
+    pub history: Vec<Generation>,
+    /// The fittest of the generations' best weight vectors, after all of
+    /// them played the same games.
+    pub best: Weights,
+    /// The fitness of `best` in those games.
+    pub best_fitness: f64,
+}
+
+impl TuningResults {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("results serialize to JSON")
+    }
+}
+
+/// Evolves bot weights with a genetic algorithm. Every weight vector plays
+/// seeded games with a bot that only looks at the current piece.
+pub struct Tuner {
+    settings: TunerSettings,
+    population: Vec<Weights>,
+    history: Vec<Generation>,
+    rng: StdRng,
+}
+
+impl Tuner {
+    /// Starts from `start` and mutations of it.
+    pub fn new(settings: TunerSettings, start: Weights) -> Self {
+        let mut rng = StdRng::seed_from_u64(settings.seed);
+        let mut population = vec![start.clone()];
+        while population.len() < settings.population.max(1) {
+            population.push(mutate(&start, settings.mutation, &mut rng));
+        }
+        Tuner {
+            settings,
+            population,
+            history: Vec::new(),
+            rng,
+        }
+    }
+
+    pub fn history(&self) -> &[Generation] {
+        &self.history
+    }
+
+    /// Plays and judges the current population, then breeds the next one.
+    pub fn step(&mut self) -> &Generation {
+        let generation = self.history.len();
+        let seeds: Vec<u64> = (0..self.settings.games as u64)
+            .map(|game| self.settings.seed ^ ((generation as u64) << 32) ^ game)
+            .collect();
+        let fitness = self.evaluate_population(&seeds);
+        let mut ranked: Vec<(f64, Weights)> =
+            fitness.into_iter().zip(self.population.drain(..)).collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mean_fitness =
+            ranked.iter().map(|(fitness, _)| fitness).sum::<f64>() / ranked.len() as f64;
+        self.history.push(Generation {
+            generation,
+            best_fitness: ranked[0].0,
+            mean_fitness,
+            best: ranked[0].1.clone(),
+        });
+        let elite = self.settings.elite.clamp(1, ranked.len());
+        self.population = ranked[..elite]
+            .iter()
+            .map(|(_, weights)| weights.clone())
+            .collect();
+        while self.population.len() < ranked.len() {
+            let a = tournament(&ranked, &mut self.rng);
+            let b = tournament(&ranked, &mut self.rng);
+            let child = crossover(a, b, &mut self.rng);
+            self.population
+                .push(mutate(&child, self.settings.mutation, &mut self.rng));
+        }
+        &self.history[generation]
+    }
+
+    /// Runs the configured number of generations.
+    pub fn run(mut self) -> TuningResults {
+        while self.history.len() < self.settings.generations {
+            self.step();
+        }
+        self.results()
+    }
+
+    /// Picks the best weights. Every generation was judged on its own games,
+    /// so the best of each generation plays the same final games again
+    /// before they are compared.
+    pub fn results(&self) -> TuningResults {
+        let mut finalists: Vec<Weights> = Vec::new();
+        for generation in self.history.iter() {
+            if !finalists.contains(&generation.best) {
+                finalists.push(generation.best.clone());
+            }
+        }
+        if finalists.is_empty() {
+            finalists.push(self.population[0].clone());
+        }
+        let fitness = self.evaluate_all(&finalists, &self.final_seeds());
+        let (best_fitness, best) = fitness
+            .into_iter()
+            .zip(finalists)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .expect("there is at least one finalist");
+        TuningResults {
+            settings: self.settings.clone(),
+            history: self.history.clone(),
+            best,
+            best_fitness,
+        }
+    }
+
+    /// Seeds of the games the finalists play, unlike those of any generation.
+    fn final_seeds(&self) -> Vec<u64> {
+        (0..self.settings.games as u64)
+            .map(|game| self.settings.seed ^ (1 << 63) ^ game)
+            .collect()
+    }
+
+    fn evaluate_population(&self, seeds: &[u64]) -> Vec<f64> {
+        self.evaluate_all(&self.population, seeds)
+    }
+
+    fn evaluate_all(&self, candidates: &[Weights], seeds: &[u64]) -> Vec<f64> {
+        let chunk = candidates.len().div_ceil(self.settings.threads.max(1));
+        thread::scope(|scope| {
+            let handles: Vec<_> = candidates
+                .chunks(chunk)
+                .map(|chunk| {
+                    scope.spawn(|| {
+                        chunk
+                            .iter()
+                            .map(|weights| evaluate(weights, &self.settings, seeds))
+                            .collect::<Vec<f64>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("evaluation thread panicked"))
+                .collect()
+        })
+    }
+}
+
+/// The average fitness of `weights` over games with the given seeds.
+pub fn evaluate(weights: &Weights, settings: &TunerSettings, seeds: &[u64]) -> f64 {
+    let total: f64 = seeds
+        .iter()
+        .map(|seed| {
+            let game = play(weights, settings.max_pieces, *seed);
+            match settings.fitness {
+                Fitness::Lines => game.lines_removed() as f64,
+                Fitness::Score => game.score() as f64,
+            }
+        })
+        .sum();
+    total / seeds.len().max(1) as f64
+}
+
+fn play(weights: &Weights, max_pieces: usize, seed: u64) -> Game {
+    let mut game = Game::with_seed(Ruleset::default(), seed);
+    let bot = Bot::new(BotSettings {
+        width: 1,
+        depth: 1,
+        previews: 0,
+        weights: weights.clone(),
+    });
+    let mut runner = BotRunner::new(bot, None);
+    while game.pieces_locked() < max_pieces && runner.tick(&mut game) {}
+    game
+}
+
+fn genes(weights: &Weights) -> Vec<f64> {
+    vec![
+        weights.height,
+        weights.danger,
+        weights.holes,
+        weights.bumpiness,
+        weights.well,
+        weights.tspin_slot,
+        weights.attack,
+        weights.burn,
+        weights.combo,
+        weights.back_to_back,
+    ]
+}
+
+fn from_genes(genes: &[f64]) -> Weights {
+    Weights {
+        height: genes[0],
+        danger: genes[1],
+        holes: genes[2],
+        bumpiness: genes[3],
+        well: genes[4],
+        tspin_slot: genes[5],
+        attack: genes[6],
+        burn: genes[7],
+        combo: genes[8],
+        back_to_back: genes[9],
+    }
+}
+
+/// Picks the fittest of three random weight vectors.
+fn tournament<'a>(ranked: &'a [(f64, Weights)], rng: &mut StdRng) -> &'a Weights {
+    // `ranked` is sorted, so the lowest index is the fittest.
+    let index = (0..3)
+        .map(|_| rng.gen_range(0..ranked.len()))
+        .min()
+        .unwrap();
+    &ranked[index].1
+}
+
+/// Takes every weight from either parent.
+fn crossover(a: &Weights, b: &Weights, rng: &mut StdRng) -> Weights {
+    let genes: Vec<f64> = genes(a)
+        .into_iter()
+        .zip(genes(b))
+        .map(|(a, b)| if rng.gen() { a } else { b })
+        .collect();
+    from_genes(&genes)
+}
+
+/// Moves some weights by normally distributed amounts.
+fn mutate(weights: &Weights, strength: f64, rng: &mut StdRng) -> Weights {
+    let genes: Vec<f64> = genes(weights)
+        .into_iter()
+        .map(|gene| {
+            if rng.gen_bool(0.3) {
+                gene + normal(rng) * strength * gene.abs().max(1.0)
+            } else {
+                gene
+            }
+        })
+        .collect();
+    from_genes(&genes)
+}
+
+/// A standard normal sample, by the Box-Muller transform.
+fn normal(rng: &mut StdRng) -> f64 {
+    let u: f64 = 1.0 - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_settings() -> TunerSettings {
+        TunerSettings {
+            population: 3,
+            elite: 1,
+            generations: 2,
+            games: 1,
+            max_pieces: 12,
+            threads: 2,
+            ..TunerSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_genes_roundtrip() {
+        let weights = Weights::default();
+        assert_eq!(from_genes(&genes(&weights)), weights);
+    }
+
+    #[test]
+    fn test_fitness_from_str() {
+        assert_eq!("score".parse::<Fitness>().unwrap(), Fitness::Score);
+        assert_eq!(Fitness::Lines.to_string(), "lines");
+        assert!("speed".parse::<Fitness>().is_err());
+    }
+
+    #[test]
+    fn test_bad_weights_are_less_fit() {
+        let settings = small_settings();
+        let bad = Weights {
+            holes: 10.0,
+            height: 2.0,
+            ..Weights::default()
+        };
+        let seeds = [1, 2];
+        assert!(
+            evaluate(&Weights::default(), &settings, &seeds) > evaluate(&bad, &settings, &seeds)
+        );
+    }
+
+    #[test]
+    fn test_run_is_deterministic() {
+        let a = Tuner::new(small_settings(), Weights::default()).run();
+        let b = Tuner::new(small_settings(), Weights::default()).run();
+        assert_eq!(a.history.len(), 2);
+        assert_eq!(a, b);
+        let json: serde_json::Value = serde_json::from_str(&a.to_json()).unwrap();
+        assert_eq!(json["settings"]["fitness"], "lines");
+    }
+
+    #[test]
+    fn test_finalists_play_the_same_games() {
+        let settings = TunerSettings {
+            max_pieces: 30,
+            ..small_settings()
+        };
+        let mut tuner = Tuner::new(settings.clone(), Weights::default());
+        tuner.step();
+        tuner.step();
+        let results = tuner.results();
+        let seeds = tuner.final_seeds();
+        let best_fitness = results
+            .history
+            .iter()
+            .map(|generation| evaluate(&generation.best, &settings, &seeds))
+            .max_by(f64::total_cmp)
+            .unwrap();
+        assert_eq!(results.best_fitness, best_fitness);
+        assert_eq!(evaluate(&results.best, &settings, &seeds), best_fitness);
+    }
+}
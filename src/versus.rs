@@ -0,0 +1,375 @@
+use crate::attack::AttackTable;
+use crate::game::Game;
+use crate::garbage::GarbageGenerator;
+use crate::ruleset::Ruleset;
+use std::collections::VecDeque;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct VersusSettings {
+    pub attack_table: AttackTable,
+    /// Ticks incoming garbage waits before it can enter the board.
+    pub garbage_delay: u32,
+    /// Probability that an attack has its hole in a different column than
+    /// the previous one.
+    pub messiness: f64,
+    /// Whether clears send garbage. Without garbage players just race.
+    pub send_garbage: bool,
+    /// The first player to clear this many lines wins.
+    pub line_goal: Option<usize>,
+}
+
+impl VersusSettings {
+    /// A race to 40 lines without garbage.
+    pub fn race() -> Self {
+        VersusSettings {
+            send_garbage: false,
+            line_goal: Some(40),
+            ..VersusSettings::default()
+        }
+    }
+}
+
+impl Default for VersusSettings {
+    fn default() -> Self {
+        VersusSettings {
+            attack_table: AttackTable::Guideline,
+            garbage_delay: 20,
+            messiness: 0.3,
+            send_garbage: true,
+            line_goal: None,
+        }
+    }
+}
+
+/// An attack waiting to enter a player's board.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct IncomingGarbage {
+    pub lines: u32,
+    pub ticks_left: u32,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum VersusState {
+    Playing,
+    Won(usize),
+    /// All remaining players topped out in the same tick.
+    Draw,
+}
+
+struct Player {
+    game: Game,
+    garbage: GarbageGenerator,
+    incoming: VecDeque<IncomingGarbage>,
+    attack_seen: u32,
+    pieces_seen: usize,
+    alive: bool,
+}
+
+impl Player {
+    fn new(game: Game, garbage: GarbageGenerator) -> Self {
+        Player {
+            game,
+            garbage,
+            incoming: VecDeque::new(),
+            attack_seen: 0,
+            pieces_seen: 0,
+            alive: true,
+        }
+    }
+
+    /// Returns the attack since the last call after cancelling it against
+    /// incoming garbage.
+    fn take_attack(&mut self) -> u32 {
+        let mut attack = self.game.attack_sent() - self.attack_seen;
+        self.attack_seen = self.game.attack_sent();
+        while attack > 0 {
+            match self.incoming.front_mut() {
+                Some(incoming) if incoming.lines > attack => {
+                    incoming.lines -= attack;
+                    attack = 0;
+                }
+                Some(incoming) => {
+                    attack -= incoming.lines;
+                    self.incoming.pop_front();
+                }
+                None => break,
+            }
+        }
+        attack
+    }
+
+    /// Lets garbage whose delay has passed enter the board if a piece was
+    /// locked without clearing lines.
+    fn receive_garbage(&mut self) {
+        let locked = self.game.pieces_locked() > self.pieces_seen;
+        self.pieces_seen = self.game.pieces_locked();
+        let cleared = self.game.last_clear().is_some_and(|clear| clear.lines > 0);
+        if !locked || cleared {
+            return;
+        }
+        while self
+            .incoming
+            .front()
+            .is_some_and(|incoming| incoming.ticks_left == 0)
+        {
+            let incoming = self.incoming.pop_front().unwrap();
+            let hole = self.garbage.next_hole();
+            if !self.game.push_garbage(incoming.lines as usize, hole) {
+                self.alive = false;
+                return;
+            }
+        }
+    }
+
+    fn count_down_garbage(&mut self) {
+        for incoming in self.incoming.iter_mut() {
+            incoming.ticks_left = incoming.ticks_left.saturating_sub(1);
+        }
+    }
+}
+
+/// Games side by side where clearing lines sends garbage to the next
+/// player still alive. Outgoing attacks first cancel garbage that is still
+/// waiting to enter the attacker's own board.
+pub struct Versus {
+    players: Vec<Player>,
+    settings: VersusSettings,
+    state: VersusState,
+}
+
+impl Versus {
+    /// Two players with random pieces and garbage.
+    pub fn new(settings: VersusSettings) -> Self {
+        let players = (0..2)
+            .map(|_| {
+                let game = Game::new();
+                let garbage = GarbageGenerator::new(settings.messiness, game.board().width());
+                Player::new(game, garbage)
+            })
+            .collect();
+        Self::with_players(settings, players)
+    }
+
+    /// Any number of players whose pieces and garbage are determined by
+    /// `seed`. All players get the same piece sequence.
+    pub fn with_seed(settings: VersusSettings, players: usize, seed: u64) -> Self {
+        let players = (0..players)
+            .map(|index| {
+                let game = Game::with_seed(Ruleset::default(), seed);
+                let garbage = GarbageGenerator::with_seed(
+                    settings.messiness,
+                    game.board().width(),
+                    seed.wrapping_add(index as u64 + 1),
+                );
+                Player::new(game, garbage)
+            })
+            .collect();
+        Self::with_players(settings, players)
+    }
+
+    fn with_players(settings: VersusSettings, mut players: Vec<Player>) -> Self {
+        for player in players.iter_mut() {
+            player.game.set_attack_table(settings.attack_table);
+        }
+        Versus {
+            players,
+            settings,
+            state: VersusState::Playing,
+        }
+    }
+
+    pub fn players(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn is_alive(&self, player: usize) -> bool {
+        self.players[player].alive
+    }
+
+    /// Makes a player lose, for example because they disconnected.
+    pub fn forfeit(&mut self, player: usize) {
+        self.players[player].alive = false;
+        self.update_state();
+    }
+
+    pub fn game(&self, player: usize) -> &Game {
+        &self.players[player].game
+    }
+
+    /// For applying a player's input between ticks.
+    pub fn game_mut(&mut self, player: usize) -> &mut Game {
+        &mut self.players[player].game
+    }
+
+    pub fn incoming(&self, player: usize) -> &VecDeque<IncomingGarbage> {
+        &self.players[player].incoming
+    }
+
+    /// Total lines of garbage waiting to enter a player's board.
+    pub fn incoming_lines(&self, player: usize) -> u32 {
+        self.incoming(player)
+            .iter()
+            .map(|incoming| incoming.lines)
+            .sum()
+    }
+
+    pub fn state(&self) -> VersusState {
+        self.state
+    }
+
+    /// Ticks all games, exchanges garbage and decides the winner once only
+    /// one player is left or someone reached the line goal.
+    pub fn tick(&mut self) -> VersusState {
+        if self.state != VersusState::Playing {
+            return self.state;
+        }
+        for player in self.players.iter_mut().filter(|player| player.alive) {
+            player.alive = player.game.tick();
+        }
+        let attacks: Vec<u32> = self
+            .players
+            .iter_mut()
+            .map(|player| player.take_attack())
+            .collect();
+        for player in self.players.iter_mut().filter(|player| player.alive) {
+            player.receive_garbage();
+            player.count_down_garbage();
+        }
+        if self.settings.send_garbage {
+            for (attacker, attack) in attacks.into_iter().enumerate() {
+                if let Some(target) = self.target(attacker).filter(|_| attack > 0) {
+                    self.players[target].incoming.push_back(IncomingGarbage {
+                        lines: attack,
+                        ticks_left: self.settings.garbage_delay,
+                    });
+                }
+            }
+        }
+        self.update_state();
+        self.state
+    }
+
+    /// The next player after `attacker` that is still alive.
+    fn target(&self, attacker: usize) -> Option<usize> {
+        (1..self.players.len())
+            .map(|offset| (attacker + offset) % self.players.len())
+            .find(|index| self.players[*index].alive)
+    }
+
+    fn update_state(&mut self) {
+        let goal_reached = self.settings.line_goal.and_then(|goal| {
+            self.players
+                .iter()
+                .position(|player| player.alive && player.game.lines_removed() >= goal)
+        });
+        let mut alive = (0..self.players.len()).filter(|index| self.players[*index].alive);
+        self.state = match (goal_reached, alive.next(), alive.next()) {
+            (Some(winner), _, _) => VersusState::Won(winner),
+            (None, None, _) => VersusState::Draw,
+            (None, Some(winner), None) if self.players.len() > 1 => VersusState::Won(winner),
+            _ => VersusState::Playing,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::tests::clear_four_lines;
+
+    fn settings(garbage_delay: u32) -> VersusSettings {
+        VersusSettings {
+            garbage_delay,
+            ..VersusSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_garbage_is_sent_after_delay() {
+        let mut versus = Versus::new(settings(2));
+        clear_four_lines(versus.game_mut(0));
+        versus.tick();
+        assert_eq!(versus.incoming_lines(1), 4);
+        assert_eq!(versus.incoming_lines(0), 0);
+
+        versus.game_mut(1).hard_drop();
+        versus.tick();
+        assert_eq!(versus.game(1).board().garbage_lines(), 0);
+        versus.tick();
+        versus.game_mut(1).hard_drop();
+        versus.tick();
+        assert_eq!(versus.game(1).board().garbage_lines(), 4);
+        assert_eq!(versus.incoming_lines(1), 0);
+    }
+
+    #[test]
+    fn test_garbage_is_cancelled() {
+        let mut versus = Versus::new(settings(100));
+        clear_four_lines(versus.game_mut(0));
+        versus.tick();
+        clear_four_lines(versus.game_mut(0));
+        versus.tick();
+        assert_eq!(versus.incoming_lines(1), 9);
+        clear_four_lines(versus.game_mut(1));
+        versus.tick();
+        assert_eq!(versus.incoming(1).len(), 1);
+        assert_eq!(versus.incoming_lines(1), 5);
+        assert_eq!(versus.incoming_lines(0), 0);
+    }
+
+    #[test]
+    fn test_top_out_decides_winner() {
+        let mut versus = Versus::new(settings(0));
+        while versus.tick() == VersusState::Playing {
+            versus.game_mut(1).hard_drop();
+        }
+        assert_eq!(versus.state(), VersusState::Won(0));
+        assert_eq!(versus.tick(), VersusState::Won(0));
+    }
+
+    #[test]
+    fn test_garbage_goes_to_next_player_alive() {
+        let mut versus = Versus::with_seed(settings(100), 3, 1);
+        clear_four_lines(versus.game_mut(2));
+        versus.tick();
+        assert_eq!(versus.incoming_lines(0), 4);
+        versus.forfeit(0);
+        assert_eq!(versus.state(), VersusState::Playing);
+        clear_four_lines(versus.game_mut(2));
+        versus.tick();
+        assert_eq!(versus.incoming_lines(1), 5);
+        versus.forfeit(1);
+        assert_eq!(versus.state(), VersusState::Won(2));
+    }
+
+    #[test]
+    fn test_race() {
+        let mut versus = Versus::with_seed(
+            VersusSettings {
+                line_goal: Some(8),
+                ..VersusSettings::race()
+            },
+            2,
+            1,
+        );
+        clear_four_lines(versus.game_mut(1));
+        assert_eq!(versus.tick(), VersusState::Playing);
+        assert_eq!(versus.incoming_lines(0), 0);
+        clear_four_lines(versus.game_mut(1));
+        assert_eq!(versus.tick(), VersusState::Won(1));
+    }
+
+    #[test]
+    fn test_seeded_players_get_same_pieces() {
+        let mut versus = Versus::with_seed(settings(0), 2, 9);
+        for _ in 0..10 {
+            assert_eq!(
+                versus.game(0).current_tetromino(),
+                versus.game(1).current_tetromino()
+            );
+            versus.game_mut(0).hard_drop();
+            versus.game_mut(1).hard_drop();
+            versus.tick();
+        }
+    }
+}